@@ -1,90 +1,302 @@
-use crate::data::*;
+use std::sync::atomic::AtomicUsize;
+use clap::{CommandFactory,FromArgMatches};
+use crate::{
+	data::*,
+	message::*,
+	config::{load_config,ConfigOptions,WheelOptions},
+	colorspace::DEFAULT_LIGHTNESS,
+	draw_aa::DEFAULT_AA_THRESHOLD,
+	palette::Gradient,
+	scheme::Scheme
+};
+
+/// 言語設定を反映したヘルプでコマンドライン引数を解析する。
+pub fn parse_args() -> Args {
+	let matches = localize_command(Args::command()).get_matches();
+	match Args::from_arg_matches(&matches) {
+		Ok(a) => a,
+		Err(e) => e.exit()
+	}
+}
 
 /// コマンドラインパーサ (Args) から内部のデータ管理型 (Status) を生成する。
+/// コマンドラインで指定されなかった値は、設定ファイルのプリセット、設定ファイルの既定値、組み込みの既定値の順に補う。
 pub fn init_status(a:&Args) -> Status {
 
-	let dm = match a.draw_mode {
+	let c = load_config(a.preset.as_deref());
+
+	let edm = a.draw_mode.clone().or_else(|| c.mode.map(|m| m.draw_mode()));
+	let (dm,aa) = init_draw_mode(edm,a,&c).unwrap_or_else(|e| error!(e));
+
+	// 実行中に切り替えられるように、他の描画モードの状態も用意しておく
+	// 使っていない描画モードの設定が不正でも起動を妨げないよう、警告を出して組み込みの既定値を使う
+	let other_modes = ModeName::ALL.iter()
+		.filter(|m| **m!=dm.name())
+		.map(|m| init_draw_mode(Some(m.draw_mode()),a,&c).unwrap_or_else(|e| {
+			eprintln!("{} {} ({})",Msg::ConfigModeIgnored,m.keyword(),e);
+			init_draw_mode(Some(m.draw_mode()),a,&ConfigOptions::default()).unwrap()
+		}))
+		.collect();
+
+	let is_hdr = matches!(&dm,DM::Colorbar(cbs) if matches!(cbs.mode,CBSMode::HDR(_)));
+
+	let mut s = Status {
+		size: (0,0),
+		draw_mode: dm,
+		terminal: a.terminal.or(c.terminal).unwrap_or(TM::Texts),
+		color: a.color.or(c.color).unwrap_or(TC::Auto),
+		pixels: a.pixels.or(c.pixels).unwrap_or(TP::Single),
+		aa,
+		linear_aa: a.linear_aa.or(c.linear_aa).unwrap_or(true),
+		aa_pattern: a.aa_pattern.or(c.aa_pattern).unwrap_or(AP::Grid),
+		aa_filter: a.aa_filter.or(c.aa_filter).unwrap_or(AF::Box),
+		aa_threshold: match flag(a.adaptive_aa,a.no_adaptive_aa,c.adaptive_aa) {
+			true  => Some(a.aa_threshold.or(c.aa_threshold).unwrap_or(DEFAULT_AA_THRESHOLD)),
+			false => None
+		},
+		samples: AtomicUsize::new(0),
+		other_modes,
+		output: a.output.as_ref().map(String::from),
+		shot_scale: a.shot_scale.or(c.shot_scale).unwrap_or(8).max(1),
+		threads: a.threads.or(c.threads).unwrap_or(0),
+		kernel: a.kernel.or(c.kernel).unwrap_or(KN::Simd),
+		precision: a.precision.or(c.precision).unwrap_or(PR::F64),
+		palette: init_palette(a,&c),
+		scheme: init_scheme(a,&c),
+		levels: a.levels.or(c.levels).unwrap_or(VL::Full),
+		// HDR のカラーバーは既定で16bitの PNG にする
+		depth: match a.depth.or(c.depth).unwrap_or(if is_hdr { 16 } else { 8 }) {
+			d @ (8|16) => d,
+			_ => error!(Msg::DepthInvalid)
+		},
+		hud: HudStatus {
+			visible: flag(a.hud,a.no_hud,c.hud),
+			help: false,
+			mouse: None,
+			frame_time: 0.0
+		}
+	};
+
+	if a.output.is_some() {
+		match (a.width.or(c.width),a.height.or(c.height)) {
+			(None,None) => { error!(Msg::ImageSizeMissing); },
+			(None,Some(_)) => { error!(Msg::ImageWidthMissing); },
+			(Some(_),None) => { error!(Msg::ImageHeightMissing); },
+			(Some(0),Some(0)) => { error!(Msg::ImageSizeInvalid); },
+			(Some(0),Some(y)) if y>0 => { error!(Msg::ImageWidthInvalid); },
+			(Some(x),Some(0)) if x>0 => { error!(Msg::ImageHeightInvalid); },
+			(Some(x),Some(y)) if x>0 && y>0 => { s.size = (x,y); },
+			_ => { panic!(); }
+		}
+	}
+
+	s
+}
+
+/// --flag と --no-flag の指定を設定ファイルより優先し、どちらもなければ設定ファイルの値、既定値は false とする。
+fn flag(yes:bool,no:bool,config:Option<bool>) -> bool {
+	match (yes,no) {
+		(true,_) => true,
+		(_,true) => false,
+		_ => config.unwrap_or(false)
+	}
+}
+
+/// パレットを読み込む。指定されていなければ None とする。
+fn init_palette(a:&Args,c:&ConfigOptions) -> Option<PaletteStatus> {
+	let spec = a.palette.as_ref().or(c.palette.as_ref())?;
+	match Gradient::load(spec) {
+		Ok(g) => Some(PaletteStatus {
+			gradient: g,
+			offset: a.palette_offset.or(c.palette_offset).unwrap_or(0.0),
+			repeat: a.palette_repeat.or(c.palette_repeat).unwrap_or(1.0)
+		}),
+		Err(e) => error!(format!("{} {}",Msg::PaletteInvalid,e))
+	}
+}
+
+/// 端末の配色を読み込む。指定されていなければ xterm とする。
+fn init_scheme(a:&Args,c:&ConfigOptions) -> Scheme {
+	let spec = a.ansi_scheme.as_ref().or(c.ansi_scheme.as_ref()).map(|s| s.as_str()).unwrap_or("xterm");
+	match Scheme::load(spec) {
+		Ok(scheme) => scheme,
+		Err(e) => error!(format!("{} {}",Msg::SchemeInvalid,e))
+	}
+}
+
+/// 色相環・色相図の状態を生成する。色空間と明度・彩度はコマンドライン、設定ファイル、既定値の順に適用する。
+fn color_status(angle:f64,mode:CSMode,space:Option<CSP>,lightness:Option<f64>,chroma:Option<f64>,w:&WheelOptions) -> ColorStatus {
+	let space = space.or(w.space).unwrap_or(CSP::Rgb);
+	ColorStatus {
+		angle,
+		mode,
+		space,
+		lightness: lightness.or(w.lightness).unwrap_or(DEFAULT_LIGHTNESS),
+		chroma: chroma.or(w.chroma).unwrap_or(space.default_chroma()),
+		mouse_position: (0.0,0.0)
+	}
+}
+
+/// 描画モードの状態とアンチエイリアスのレベルを生成する。
+/// 指定された値が不正な場合はエラーメッセージを返す。
+fn init_draw_mode(edm:Option<EDM>,a:&Args,c:&ConfigOptions) -> Result<(DM,u8),String> {
+
+	let dm = match edm {
 		Some(EDM::Ansi)|None => DM::Ansi,
-		Some(EDM::Hue {rotate:r}) => {
-			let s = ColorStatus {
-				angle: r,
-				mode:  CSMode::ConicW,
-				mouse_position: (0.0,0.0)
-			};
-			DM::Color(s)
+		Some(EDM::Hue {rotate:r,space,lightness,chroma}) => {
+			let angle = r.or(c.hue.rotate).unwrap_or(0.0);
+			DM::Color(color_status(angle,CSMode::ConicW,space,lightness,chroma,&c.hue.wheel()))
 		},
-		Some(EDM::Conic {rotate:r}) => {
-			let s = ColorStatus {
-				angle: r,
-				mode: CSMode::Conic,
-				mouse_position: (0.0,0.0)
-			};
-			DM::Color(s)
+		Some(EDM::Conic {rotate:r,space,lightness,chroma}) => {
+			let angle = r.or(c.conic.rotate).unwrap_or(0.0);
+			DM::Color(color_status(angle,CSMode::Conic,space,lightness,chroma,&c.conic.wheel()))
+		},
+		Some(EDM::BVW {offset:o,space,lightness,chroma}) => {
+			let angle = o.or(c.bvw.offset).unwrap_or(0.0);
+			DM::Color(color_status(angle,CSMode::BVW,space,lightness,chroma,&c.bvw.wheel()))
+		},
+		Some(EDM::BV {offset:o,space,lightness,chroma}) => {
+			let angle = o.or(c.bv.offset).unwrap_or(0.0);
+			DM::Color(color_status(angle,CSMode::BV,space,lightness,chroma,&c.bv.wheel()))
 		},
-		Some(EDM::BVW {offset:o}) => {
-			let s = ColorStatus {
-				angle: o,
-				mode: CSMode::BVW,
-				mouse_position: (0.0,0.0)
+		Some(EDM::Smpte {repeat,no_repeat,hd,no_hd,patch}) => {
+			let s = ColorbarStatus {
+				mode: match flag(hd,no_hd,c.smpte.hd) {
+					true  => CBSMode::RP219(patch.or(c.smpte.patch).unwrap_or(HdPatch::PlusI)),
+					false => CBSMode::SMPTE
+				},
+				repeat: flag(repeat,no_repeat,c.smpte.repeat)
 			};
-			DM::Color(s)
+			DM::Colorbar(s)
 		},
-		Some(EDM::BV {offset:o}) => {
-			let s = ColorStatus {
-				angle: o,
-				mode: CSMode::BV,
-				mouse_position: (0.0,0.0)
+		Some(EDM::Arib {repeat,no_repeat}) => {
+			let s = ColorbarStatus {
+				mode: CBSMode::ARIB,
+				repeat: flag(repeat,no_repeat,c.arib.repeat)
 			};
-			DM::Color(s)
+			DM::Colorbar(s)
 		},
-		Some(EDM::Smpte {repeat:r}) => {
+		Some(EDM::Ebu {repeat,no_repeat,full,no_full,split,no_split}) => {
 			let s = ColorbarStatus {
-				mode: CBSMode::SMPTE,
-				repeat: r
+				mode: CBSMode::EBU {
+					full:  flag(full,no_full,c.ebu.full),
+					split: flag(split,no_split,c.ebu.split)
+				},
+				repeat: flag(repeat,no_repeat,c.ebu.repeat)
 			};
 			DM::Colorbar(s)
 		},
-		Some(EDM::Arib {repeat:r}) => {
+		Some(EDM::Hdr {repeat,no_repeat,transfer}) => {
 			let s = ColorbarStatus {
-				mode: CBSMode::ARIB,
-				repeat: r
+				mode: CBSMode::HDR(transfer.or(c.hdr.transfer).unwrap_or(Transfer::Pq)),
+				repeat: flag(repeat,no_repeat,c.hdr.repeat)
 			};
 			DM::Colorbar(s)
 		},
-		Some(EDM::Mandelbrot) => DM::Mandelbrot,
-		Some(EDM::Newton {p,tau,max, speed}) => {
-			DM::NewtonApprox(NewtonApproxStatus {p,tau,max,speed})
+		Some(EDM::Mandelbrot {re,im,zoom,max,distance,no_distance}) => {
+			let re = re.or_else(|| c.mandelbrot.re.clone()).unwrap_or(String::from("-0.5"));
+			let im = im.or_else(|| c.mandelbrot.im.clone()).unwrap_or(String::from("0"));
+			let zoom = zoom.or(c.mandelbrot.zoom).unwrap_or(1.0);
+			let max = max.or(c.mandelbrot.max).unwrap_or(200);
+			let distance = flag(distance,no_distance,c.mandelbrot.distance);
+			match MandelbrotStatus::new(&re,&im,zoom,max,distance) {
+				Some(ms) => DM::Mandelbrot(ms),
+				None => { return Err(format!("{} {} {}",Msg::CenterInvalid,re,im)); }
+			}
+		},
+		Some(EDM::Newton {p,tau,max,speed,no_speed}) => {
+			let p = p.or(c.newton.p).unwrap_or(6);
+			if p<2 { return Err(format!("{} {}",Msg::DegreeInvalid,p)); }
+			DM::NewtonApprox(NewtonApproxStatus {
+				p,
+				tau:   tau.or(c.newton.tau).unwrap_or(0.1),
+				max:   max.or(c.newton.max).unwrap_or(1000),
+				speed: flag(speed,no_speed,c.newton.speed)
+			})
 		}
-		Some(EDM::Help) => { panic!(); }
+		Some(EDM::Cie {uv,no_uv,white}) => {
+			DM::Cie(CieStatus {
+				uv:    flag(uv,no_uv,c.cie.uv),
+				white: white.or(c.cie.white).unwrap_or(WP::D65)
+			})
+		},
+		Some(EDM::Bench {..})|Some(EDM::Colors {..})|Some(EDM::Help) => { panic!(); }
 	};
 
-	let aa = match &dm {
-		DM::Ansi|DM::Colorbar(_) => 0,
-		_ => a.aa
+	// ANSIテストとカラーバーは境目の色を混ぜないよう、指定された場合だけアンチエイリアスを掛ける
+	let aa = match (&dm,a.aa.or(c.aa)) {
+		(_,Some(aa)) => aa,
+		(DM::Ansi|DM::Colorbar(_),None) => 0,
+		(_,None) => 2
 	};
 
-	let mut s = Status {
-		size: (0,0),
-		draw_mode: dm,
-		terminal: a.terminal,
-		color: a.color,
-		pixels: a.pixels,
-		aa: aa,
-		output: a.output.as_ref().map(|s| String::from(s))
-	};
+	Ok((dm,aa))
 
-	if a.output!=None {
-		match (a.width,a.height) {
-			(None,None) => { error!("画像サイズが指定されていません"); },
-			(None,Some(_)) => { error!("画像の横幅が指定されていません"); },
-			(Some(_),None) => { error!("画像の縦幅が指定されていません"); },
-			(Some(0),Some(0)) => { error!("画像サイズが不正です"); },
-			(Some(0),Some(y)) if y>0 => { error!("画像の横幅が不正です"); },
-			(Some(x),Some(0)) if x>0 => { error!("画像の縦幅が不正です"); },
-			(Some(x),Some(y)) if x>0 && y>0 => { s.size = (x,y); },
-			_ => { panic!(); }
-		}
-	}
+}
+
+/// 性能測定用に、画像出力と同じ設定で指定した描画モードの状態を生成する。
+/// 画像の大きさは --width, --height が指定されていなければ 800 × 600 とする。
+pub fn init_bench_status(a:&Args,mode:ModeName) -> Status {
+
+	let c = load_config(a.preset.as_deref());
+	let (dm,aa) = init_draw_mode(Some(mode.draw_mode()),a,&c).unwrap_or_else(|e| error!(e));
+	let size = (
+		a.width.or(c.width).unwrap_or(800).max(1),
+		a.height.or(c.height).unwrap_or(600).max(1)
+	);
 
+	let (threads,kernel,precision) = merge_cpu_options(a,&c);
+	let mut s = bench_status(dm,aa,size,threads);
+	s.kernel = kernel;
+	s.precision = precision;
+	s.palette = init_palette(a,&c);
+	s.scheme = init_scheme(a,&c);
 	s
-}
\ No newline at end of file
+
+}
+
+/// CPU 性能の測定用に、スレッド数、カーネル、精度を画像出力と同じ設定で決める。
+pub fn init_cpu_options(a:&Args) -> (usize,KN,PR) {
+	merge_cpu_options(a,&load_config(a.preset.as_deref()))
+}
+
+fn merge_cpu_options(a:&Args,c:&ConfigOptions) -> (usize,KN,PR) {
+	(
+		a.threads.or(c.threads).unwrap_or(0),
+		a.kernel.or(c.kernel).unwrap_or(KN::Simd),
+		a.precision.or(c.precision).unwrap_or(PR::F64)
+	)
+}
+
+/// 画像としてメモリ上に描画するためだけの状態を生成する。
+pub fn bench_status(dm:DM,aa:u8,size:CU,threads:usize) -> Status {
+	Status {
+		size,
+		draw_mode: dm,
+		terminal: TM::Texts,
+		color: TC::Full,
+		pixels: TP::Single,
+		aa,
+		linear_aa: true,
+		aa_pattern: AP::Grid,
+		aa_filter: AF::Box,
+		aa_threshold: None,
+		samples: AtomicUsize::new(0),
+		other_modes: vec![],
+		output: None,
+		shot_scale: 1,
+		threads,
+		kernel: KN::Simd,
+		precision: PR::F64,
+		palette: None,
+		scheme: Scheme::xterm(),
+		levels: VL::Full,
+		depth: 8,
+		hud: HudStatus {
+			visible: false,
+			help: false,
+			mouse: None,
+			frame_time: 0.0
+		}
+	}
+}
@@ -0,0 +1,182 @@
+use std::thread::available_parallelism;
+use std::time::Instant;
+use std::sync::atomic::Ordering;
+use rayon::ThreadPoolBuilder;
+
+use crate::{
+	data::*,
+	args::{init_bench_status,init_cpu_options,bench_status},
+	draw_image::frame,
+	draw_func::aa_subpixels,
+	draw_aa::DEFAULT_AA_THRESHOLD
+};
+
+pub fn bench(a:&Args,kind:&BenchKind) {
+	match kind {
+		BenchKind::Tiles {mode,repeat} => bench_tiles(a,*mode,*repeat),
+		BenchKind::Cpu {repeat} => bench_cpu(a,*repeat)
+	}
+}
+
+/// 1,2,4,… と最大スレッド数までスレッド数を変えて描画し、所要時間を表にして表示する。
+fn bench_tiles(a:&Args,mode:ModeName,repeat:usize) {
+
+	let s = init_bench_status(a,mode);
+
+	println!(
+		"{} {}x{} aa={} kernel={} precision={}",
+		mode.keyword(),s.size.0,s.size.1,s.aa,s.kernel.keyword(),s.precision.keyword()
+	);
+	println!("{:>8} {:>12} {:>10} {:>8}","threads","time[ms]","Mpx/s","speedup");
+
+	let pixels = (s.size.0 as f64)*(s.size.1 as f64);
+	let mut base:Option<f64> = None;
+
+	for n in thread_counts(max_threads(&s)) {
+		let t = time_frame(&s,n,repeat);
+		let b = *base.get_or_insert(t);
+		println!(
+			"{:>8} {:>12.2} {:>10.2} {:>8.2}",
+			n,t*1000.0,pixels/t/1e6,b/t
+		);
+	}
+
+}
+
+/// bench cpu で描画する固定の負荷 (描画モード,画像の大きさ,アンチエイリアス)
+fn cpu_workloads() -> Vec<(DM,CU,u8)> {
+	vec![
+		(DM::Mandelbrot(MandelbrotStatus::new("-0.5","0",1.0,200,false).unwrap()),(640,480),2),
+		(
+			DM::NewtonApprox(NewtonApproxStatus {p:6,tau:0.1,max:1000,speed:false}),
+			(320,240),2
+		)
+	]
+}
+
+/// 固定の負荷をスレッド数を変えて描画し、速度向上率と並列化効率を表示する。
+/// 続けて最大スレッド数での scalar と simd の計算方法、 f64 と f32 の精度、全てのピクセルとアダプティブのアンチエイリアスを比較する。
+/// スコアは最大スレッド数での1秒あたりのサンプル数 (フラグメントの評価回数) の負荷ごとの幾何平均を1000で割ったもの。
+fn bench_cpu(a:&Args,repeat:usize) {
+
+	let (threads,kernel,precision) = init_cpu_options(a);
+	let mut rates:Vec<f64> = vec![];
+	let mut max = 1;
+
+	for (dm,size,aa) in cpu_workloads() {
+
+		let mut s = bench_status(dm,aa,size,threads);
+		s.kernel = kernel;
+		s.precision = precision;
+		let samples = (size.0 as f64)*(size.1 as f64)*(aa_subpixels(aa,&size,false,AP::Grid,AF::Box).offsets.len() as f64);
+		max = max_threads(&s);
+
+		println!(
+			"{} {}x{} aa={} kernel={} precision={} ({} samples)",
+			s.draw_mode.name().keyword(),size.0,size.1,aa,kernel.keyword(),precision.keyword(),samples
+		);
+		println!("{:>8} {:>12} {:>8} {:>11}","threads","time[ms]","speedup","efficiency");
+
+		let mut base:Option<f64> = None;
+		let mut last = 0.0;
+		for n in thread_counts(max) {
+			let t = time_frame(&s,n,repeat);
+			let b = *base.get_or_insert(t);
+			println!(
+				"{:>8} {:>12.2} {:>8.2} {:>10.1}%",
+				n,t*1000.0,b/t,b/t/(n as f64)*100.0
+			);
+			last = t;
+		}
+		rates.push(samples/last);
+		println!();
+
+	}
+
+	println!("scalar / simd ({} threads, precision={})",max,precision.keyword());
+	println!("{:>10} {:>12} {:>12} {:>8}","mode","scalar[ms]","simd[ms]","speedup");
+	for (dm,size,aa) in cpu_workloads() {
+		let mut s = bench_status(dm,aa,size,threads);
+		s.precision = precision;
+		s.kernel = KN::Scalar;
+		let ts = time_frame(&s,max,repeat);
+		s.kernel = KN::Simd;
+		let tv = time_frame(&s,max,repeat);
+		println!(
+			"{:>10} {:>12.2} {:>12.2} {:>8.2}",
+			s.draw_mode.name().keyword(),ts*1000.0,tv*1000.0,ts/tv
+		);
+	}
+	println!();
+
+	println!("f64 / f32 ({} threads, kernel={})",max,kernel.keyword());
+	println!("{:>10} {:>12} {:>12} {:>8}","mode","f64[ms]","f32[ms]","speedup");
+	for (dm,size,aa) in cpu_workloads() {
+		let mut s = bench_status(dm,aa,size,threads);
+		s.kernel = kernel;
+		s.precision = PR::F64;
+		let td = time_frame(&s,max,repeat);
+		s.precision = PR::F32;
+		let tf = time_frame(&s,max,repeat);
+		println!(
+			"{:>10} {:>12.2} {:>12.2} {:>8.2}",
+			s.draw_mode.name().keyword(),td*1000.0,tf*1000.0,td/tf
+		);
+	}
+	println!();
+
+	println!("full / adaptive ({} threads, threshold={})",max,DEFAULT_AA_THRESHOLD);
+	println!("{:>10} {:>12} {:>10} {:>14} {:>10} {:>8}","mode","full[ms]","samples","adaptive[ms]","samples","speedup");
+	for (dm,size,aa) in cpu_workloads() {
+		let mut s = bench_status(dm,aa,size,threads);
+		s.kernel = kernel;
+		s.precision = precision;
+		let tf = time_frame(&s,max,repeat);
+		let nf = s.samples.load(Ordering::Relaxed);
+		s.aa_threshold = Some(DEFAULT_AA_THRESHOLD);
+		let ta = time_frame(&s,max,repeat);
+		let na = s.samples.load(Ordering::Relaxed);
+		println!(
+			"{:>10} {:>12.2} {:>10} {:>14.2} {:>10} {:>8.2}",
+			s.draw_mode.name().keyword(),tf*1000.0,nf,ta*1000.0,na,tf/ta
+		);
+	}
+	println!();
+
+	let geomean = (rates.iter().map(|r| r.ln()).sum::<f64>()/(rates.len() as f64)).exp();
+	println!("score: {:.0} ({} threads, kernel={}, precision={})",geomean/1000.0,max,kernel.keyword(),precision.keyword());
+
+}
+
+/// --threads が指定されていればその値を、そうでなければ論理コア数を最大スレッド数とする。
+pub fn max_threads(s:&Status) -> usize {
+	match s.threads {
+		0 => available_parallelism().map(|n| n.get()).unwrap_or(1),
+		n => n
+	}
+}
+
+/// 1 から max までの2の累乗と max 自身
+pub fn thread_counts(max:usize) -> Vec<usize> {
+	let mut v:Vec<usize> = (0..).map(|i| 1<<i).take_while(|n| *n<max).collect();
+	v.push(max);
+	v
+}
+
+/// n スレッドで repeat 回描画し、最も短い時間を秒で返す。
+pub fn time_frame(s:&Status,n:usize,repeat:usize) -> f64 {
+
+	let pool = match ThreadPoolBuilder::new().num_threads(n).build() {
+		Ok(p) => p,
+		Err(e) => error!(format!("{}\n{:?}",crate::message::Msg::ErrorOccurred,e))
+	};
+
+	(0..repeat.max(1))
+		.map(|_| {
+			let st = Instant::now();
+			pool.install(|| frame(s,&s.size));
+			st.elapsed().as_secs_f64()
+		})
+		.fold(f64::INFINITY,f64::min)
+
+}
@@ -0,0 +1,299 @@
+use std::ops::{Add,Sub,Mul};
+
+/// 符号と絶対値による多倍長の固定小数点数
+/// 絶対値は32bitの limb を下位から並べたもので、最上位の1つが整数部、残りが小数部を表す。
+/// 演算する2つの数は小数部の limb の数が等しくなければならない。
+#[derive(Clone)]
+pub struct BigFixed {
+	neg: bool,
+	mag: Vec<u32>
+}
+
+impl BigFixed {
+
+	pub fn zero(frac:usize) -> BigFixed {
+		BigFixed { neg:false, mag:vec![0;frac+1] }
+	}
+
+	/// 小数部の limb の数
+	pub fn frac_limbs(&self) -> usize {
+		self.mag.len()-1
+	}
+
+	/// f64 の値を誤差なく変換する。小数部に収まらない下位の桁は切り捨てる。
+	pub fn from_f64(v:f64,frac:usize) -> BigFixed {
+		let mut x = BigFixed::zero(frac);
+		let mut r = v.abs();
+		x.mag[frac] = r.trunc() as u32;
+		r = r.fract();
+		for i in (0..frac).rev() {
+			if r==0.0 { break; }
+			r *= 4294967296.0;
+			x.mag[i] = r.trunc() as u32;
+			r = r.fract();
+		}
+		x.neg = v<0.0 && !x.is_zero();
+		x
+	}
+
+	/// "-0.75", "1.5e-3" のような10進数の文字列を変換する。小数部に収まらない下位の桁は切り捨てる。
+	/// 整数部が32bitに収まらない場合や数値として読めない場合は None を返す。
+	pub fn parse(text:&str,frac:usize) -> Option<BigFixed> {
+
+		let text = text.trim();
+		let (neg,text) = match text.as_bytes().first() {
+			Some(b'-') => (true,&text[1..]),
+			Some(b'+') => (false,&text[1..]),
+			_ => (false,text)
+		};
+		let (mantissa,exp) = match text.find(['e','E']) {
+			Some(i) => (&text[..i],text[i+1..].parse::<i64>().ok()?),
+			None => (text,0)
+		};
+		let (int,fract) = mantissa.split_once('.').unwrap_or((mantissa,""));
+
+		let digits:Vec<u32> = int.chars().chain(fract.chars())
+			.map(|c| c.to_digit(10))
+			.collect::<Option<_>>()?;
+		if digits.is_empty() { return None; }
+
+		// 小数点の位置を指数の分だけずらす
+		let point = (int.len() as i64).checked_add(exp)?;
+
+		// 先頭の 0 でない桁が 10^(magnitude-1) の位になる。桁を1つずつ処理する前に、
+		// 整数部に収まらない数と、全体が小数部の最下位の limb より小さく 0 になる数を除く
+		let lead = match digits.iter().position(|d| *d!=0) {
+			Some(i) => i as i64,
+			None => { return Some(BigFixed::zero(frac)); }
+		};
+		let magnitude = point.checked_sub(lead)?;
+		if magnitude>10 { return None; }
+		if magnitude < -10*(frac as i64) { return Some(BigFixed::zero(frac)); }
+		let digit = |i:i64| if i>=0 && (i as usize)<digits.len() { digits[i as usize] } else { 0 };
+
+		let mut int_value:u64 = 0;
+		for i in point.min(0)..point {
+			int_value = int_value*10 + digit(i) as u64;
+			if int_value>u32::MAX as u64 { return None; }
+		}
+
+		let mut x = BigFixed::zero(frac);
+		for i in (point..digits.len() as i64).rev() {
+			x.mag[frac] += digit(i);
+			x.div_small(10);
+		}
+		x.mag[frac] = int_value as u32;
+		x.neg = neg && !x.is_zero();
+
+		Some(x)
+
+	}
+
+	/// 最も近い f64 の値
+	pub fn to_f64(&self) -> f64 {
+		let v = self.mag.iter().fold(0.0,|acc,l| acc/4294967296.0 + *l as f64);
+		if self.neg { -v } else { v }
+	}
+
+	/// 小数点以下 digits 桁までの10進数の文字列 (切り捨て)
+	pub fn to_decimal(&self,digits:usize) -> String {
+		let frac = self.frac_limbs();
+		let mut x = self.clone();
+		let mut text = format!("{}{}.",if self.neg { "-" } else { "" },x.mag[frac]);
+		for _ in 0..digits {
+			x.mag[frac] = 0;
+			x.mul_small(10);
+			text.push(char::from_digit(x.mag[frac],10).unwrap());
+		}
+		text
+	}
+
+	/// 小数部の limb の数を変更する。増やす場合は誤差なく、減らす場合は下位を切り捨てる。
+	pub fn resize(&self,frac:usize) -> BigFixed {
+		let cur = self.frac_limbs();
+		let mag = match frac>=cur {
+			true  => std::iter::repeat_n(0,frac-cur).chain(self.mag.iter().copied()).collect(),
+			false => self.mag[cur-frac..].to_vec()
+		};
+		let mut x = BigFixed { neg:self.neg, mag };
+		x.neg = x.neg && !x.is_zero();
+		x
+	}
+
+	fn is_zero(&self) -> bool {
+		self.mag.iter().all(|l| *l==0)
+	}
+
+	fn div_small(&mut self,d:u32) {
+		let mut rem:u64 = 0;
+		for l in self.mag.iter_mut().rev() {
+			let cur = (rem<<32) | (*l as u64);
+			*l = (cur/(d as u64)) as u32;
+			rem = cur%(d as u64);
+		}
+	}
+
+	fn mul_small(&mut self,m:u32) {
+		let mut carry:u64 = 0;
+		for l in self.mag.iter_mut() {
+			let cur = (*l as u64)*(m as u64) + carry;
+			*l = cur as u32;
+			carry = cur>>32;
+		}
+	}
+
+	/// 符号付きの加算。 other_neg で other の符号を反転して扱う。
+	fn add_signed(&self,other:&BigFixed,other_neg:bool) -> BigFixed {
+		assert_eq!(self.mag.len(),other.mag.len());
+		let x = match (self.neg==other_neg,cmp_mag(&self.mag,&other.mag).is_ge()) {
+			(true,_)      => BigFixed { neg:self.neg, mag:add_mag(&self.mag,&other.mag) },
+			(false,true)  => BigFixed { neg:self.neg, mag:sub_mag(&self.mag,&other.mag) },
+			(false,false) => BigFixed { neg:other_neg, mag:sub_mag(&other.mag,&self.mag) }
+		};
+		BigFixed { neg: x.neg && !x.is_zero(), mag: x.mag }
+	}
+
+}
+
+fn cmp_mag(a:&[u32],b:&[u32]) -> std::cmp::Ordering {
+	a.iter().rev().cmp(b.iter().rev())
+}
+
+/// 絶対値の和。整数部からあふれた桁は捨てる。
+fn add_mag(a:&[u32],b:&[u32]) -> Vec<u32> {
+	let mut carry = 0;
+	a.iter().zip(b).map(|(x,y)| {
+		let s = (*x as u64) + (*y as u64) + carry;
+		carry = s>>32;
+		s as u32
+	}).collect()
+}
+
+/// 絶対値の差。 a >= b であること。
+fn sub_mag(a:&[u32],b:&[u32]) -> Vec<u32> {
+	let mut borrow = 0;
+	a.iter().zip(b).map(|(x,y)| {
+		let d = (*x as i64) - (*y as i64) - borrow;
+		borrow = if d<0 { 1 } else { 0 };
+		d.rem_euclid(1<<32) as u32
+	}).collect()
+}
+
+impl Add for &BigFixed {
+	type Output = BigFixed;
+	fn add(self,other:&BigFixed) -> BigFixed {
+		self.add_signed(other,other.neg)
+	}
+}
+
+impl Sub for &BigFixed {
+	type Output = BigFixed;
+	fn sub(self,other:&BigFixed) -> BigFixed {
+		self.add_signed(other,!other.neg)
+	}
+}
+
+impl Mul for &BigFixed {
+	type Output = BigFixed;
+	/// 積の小数部の下位の桁は切り捨てる。整数部からあふれた桁は捨てる。
+	fn mul(self,other:&BigFixed) -> BigFixed {
+		assert_eq!(self.mag.len(),other.mag.len());
+		let n = self.mag.len();
+		let frac = n-1;
+		let mut prod = vec![0u64;n*2+1];
+		for (i,x) in self.mag.iter().enumerate() {
+			let mut carry:u64 = 0;
+			for (j,y) in other.mag.iter().enumerate() {
+				let t = prod[i+j] + (*x as u64)*(*y as u64) + carry;
+				prod[i+j] = t & 0xffffffff;
+				carry = t>>32;
+			}
+			prod[i+n] += carry;
+		}
+		let x = BigFixed {
+			neg: self.neg!=other.neg,
+			mag: prod[frac..frac+n].iter().map(|l| *l as u32).collect()
+		};
+		BigFixed { neg: x.neg && !x.is_zero(), mag: x.mag }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::BigFixed;
+
+	fn parse(text:&str) -> BigFixed {
+		BigFixed::parse(text,2).unwrap()
+	}
+
+	#[test]
+	fn parse_round_trip() {
+		assert_eq!(parse("-0.75").to_decimal(4),"-0.7500");
+		assert_eq!(parse("3.0625").to_decimal(4),"3.0625");
+		assert_eq!(parse("+12.5e-1").to_decimal(2),"1.25");
+		assert_eq!(parse("0.0078125E2").to_decimal(6),"0.781250");
+		assert_eq!(parse("-0").to_decimal(1),"0.0");
+		assert_eq!(parse("4294967295").to_decimal(0),"4294967295.");
+		assert_eq!(parse("-0.75").to_f64(),-0.75);
+		let v = parse("-0.743643887037158704752191506114774").to_f64();
+		assert!((v+0.7436438870371587).abs()<1e-16);
+		assert!((parse("1.5e-3").to_f64()-1.5e-3).abs()<1e-18);
+	}
+
+	#[test]
+	fn parse_rejects() {
+		for text in ["","-",".","abc","1.2.3","1e","4294967296","1e10","-5e99999999999","1e99999999999999999999","9e9223372036854775807"] {
+			assert!(BigFixed::parse(text,2).is_none(),"{}",text);
+		}
+	}
+
+	#[test]
+	fn parse_extreme_exponents() {
+		// 0 と、小数部の最下位の limb より小さく 0 になる数は桁を1つずつ処理せずに返す
+		assert_eq!(parse("0e99999999999").to_decimal(1),"0.0");
+		assert_eq!(parse("-1e-99999999999").to_decimal(1),"0.0");
+		assert_eq!(parse("1e-30").to_f64(),0.0);
+		assert!(BigFixed::parse("1e-30",4).unwrap().to_f64()>0.0);
+	}
+
+	#[test]
+	fn from_f64_and_resize() {
+		assert_eq!(BigFixed::from_f64(-2.5,1).to_decimal(3),"-2.500");
+		assert_eq!(BigFixed::from_f64(0.1,2).to_f64(),0.1);
+		let x = parse("0.5").resize(4);
+		assert_eq!(x.frac_limbs(),4);
+		assert_eq!(x.resize(1).to_decimal(2),"0.50");
+	}
+
+	#[test]
+	fn add_sub() {
+		assert_eq!((&parse("0.25")-&parse("0.75")).to_decimal(2),"-0.50");
+		assert_eq!((&parse("-0.25")+&parse("-1.5")).to_decimal(2),"-1.75");
+		assert_eq!((&parse("-0.25")+&parse("1.5")).to_decimal(2),"1.25");
+		assert_eq!((&parse("0.5")-&parse("0.5")).to_decimal(1),"0.0");
+		assert_eq!((&parse("-0.5")+&parse("0.5")).to_decimal(1),"0.0");
+		// limb をまたぐ繰り上がりと繰り下がり
+		let one = BigFixed { neg:false, mag:vec![0,0,1] };
+		let ulp = BigFixed { neg:false, mag:vec![1,0,0] };
+		let below = &one-&ulp;
+		assert_eq!(below.mag,vec![u32::MAX,u32::MAX,0]);
+		assert_eq!((&below+&ulp).mag,vec![0,0,1]);
+		assert!((&ulp-&one).neg);
+	}
+
+	#[test]
+	fn mul() {
+		assert_eq!((&parse("1.5")*&parse("-2.25")).to_decimal(3),"-3.375");
+		assert_eq!((&parse("-0.5")*&parse("-0.5")).to_decimal(2),"0.25");
+		assert_eq!((&parse("0")*&parse("-3")).to_decimal(1),"0.0");
+		// 小数部の下位の桁は切り捨てる: (1-2^-64)^2 = 1-2^-63+2^-128
+		let below = BigFixed { neg:false, mag:vec![u32::MAX,u32::MAX,0] };
+		assert_eq!((&below*&below).mag,vec![u32::MAX-1,u32::MAX,0]);
+		// 2^-64 の2乗は小数部に収まらず 0 になる
+		let ulp = BigFixed { neg:true, mag:vec![1,0,0] };
+		let p = &ulp*&BigFixed { neg:false, mag:vec![1,0,0] };
+		assert!(!p.neg && p.is_zero());
+	}
+
+}
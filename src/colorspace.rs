@@ -0,0 +1,313 @@
+use crate::data::*;
+
+/// CIE XYZ から sRGB (D65) の線形な値への変換行列
+const XYZ2RGB:[[f64;3];3] = [
+	[ 3.2409699419045213,-1.5373831775700935,-0.4986107602930033],
+	[-0.9692436362808798, 1.8759675015077206, 0.0415550574071756],
+	[ 0.0556300796969936,-0.2039769588889765, 1.0569715142428784]
+];
+
+/// D65 の白色点 (Y=1)
+const WHITE:(f64,f64,f64) = (0.9504559270516716,1.0,1.0890577507598784);
+
+/// CIE L*a*b*, L*u*v* の κ と ε
+const KAPPA:f64 = 24389.0/27.0;
+const EPSILON:f64 = 216.0/24389.0;
+
+/// 範囲外とみなすまでの誤差
+const GAMUT_TOLERANCE:f64 = 1e-9;
+
+impl ColorSpace {
+
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			CSP::Rgb   => "rgb",
+			CSP::Oklch => "oklch",
+			CSP::Lch   => "lch",
+			CSP::Hsluv => "hsluv"
+		}
+	}
+
+	/// 色相環・色相図の切り替えの順序で次の色空間
+	pub fn next(&self) -> CSP {
+		match self {
+			CSP::Rgb   => CSP::Oklch,
+			CSP::Oklch => CSP::Lch,
+			CSP::Lch   => CSP::Hsluv,
+			CSP::Hsluv => CSP::Rgb
+		}
+	}
+
+	/// 既定の彩度 (%)
+	pub fn default_chroma(&self) -> f64 {
+		match self {
+			CSP::Hsluv => 100.0,
+			_ => 30.0
+		}
+	}
+
+}
+
+/// 既定の明度 (%)
+pub const DEFAULT_LIGHTNESS:f64 = 70.0;
+
+/// 色空間 space の明度 l (%)、彩度 c (%)、色相 h (度) の色を sRGB に変換する。
+/// 彩度の 100% は OKLCH で 0.4、 CIELCh で 150、 HSLuv では各色相で sRGB に収まる最大の彩度とする。 rgb は明度と彩度を使わず、 deg2rgb の色相とする。
+/// 0 から 1 に切り詰めた sRGB の値と、切り詰める前に sRGB の範囲内だったかどうかを返す。
+pub fn lch_to_srgb(space:CSP,l:f64,c:f64,h:f64) -> ((f64,f64,f64),bool) {
+
+	let l = l.clamp(0.0,100.0);
+	let c = c.max(0.0);
+	let (sin,cos) = h.to_radians().sin_cos();
+
+	let linear = match space {
+		CSP::Oklch => {
+			let c = c/100.0*0.4;
+			oklab_to_linear(l/100.0,c*cos,c*sin)
+		},
+		CSP::Lch => {
+			let c = c/100.0*150.0;
+			xyz_to_linear(lab_to_xyz(l,c*cos,c*sin))
+		},
+		CSP::Hsluv => {
+			let c = max_chroma(l,h)*c.min(100.0)/100.0;
+			xyz_to_linear(luv_to_xyz(l,c*cos,c*sin))
+		},
+		CSP::Rgb => {
+			let rgb = crate::draw_lib::deg2rgb(h);
+			(srgb_decode(rgb.0),srgb_decode(rgb.1),srgb_decode(rgb.2))
+		}
+	};
+
+	let inside = |v:f64| (-GAMUT_TOLERANCE..=1.0+GAMUT_TOLERANCE).contains(&v);
+	let in_gamut = inside(linear.0) && inside(linear.1) && inside(linear.2);
+	let encode = |v:f64| srgb_encode(v.clamp(0.0,1.0));
+
+	((encode(linear.0),encode(linear.1),encode(linear.2)),in_gamut)
+
+}
+
+/// sRGB の伝達関数 (線形な値から符号化された値へ)
+pub fn srgb_encode(v:f64) -> f64 {
+	match v {
+		v if v<=0.0031308 => v*12.92,
+		v => 1.055*v.powf(1.0/2.4)-0.055
+	}
+}
+
+/// sRGB の伝達関数の逆 (符号化された値から線形な値へ)
+pub fn srgb_decode(v:f64) -> f64 {
+	match v {
+		v if v<=0.04045 => v/12.92,
+		v => ((v+0.055)/1.055).powf(2.4)
+	}
+}
+
+/// BT.709 から BT.2020 の線形な値への変換行列 (ITU-R BT.2087)
+const BT709_TO_BT2020:[[f64;3];3] = [
+	[0.6274,0.3293,0.0433],
+	[0.0691,0.9195,0.0114],
+	[0.0164,0.0880,0.8956]
+];
+
+/// PQ (SMPTE ST 2084) の定数
+const PQ_M1:f64 = 2610.0/16384.0;
+const PQ_M2:f64 = 2523.0/4096.0*128.0;
+const PQ_C1:f64 = 3424.0/4096.0;
+const PQ_C2:f64 = 2413.0/4096.0*32.0;
+const PQ_C3:f64 = 2392.0/4096.0*32.0;
+
+/// HLG (ITU-R BT.2100) の OETF の定数
+const HLG_A:f64 = 0.17883277;
+const HLG_B:f64 = 1.0-4.0*HLG_A;
+const HLG_C:f64 = 0.55991073;
+
+/// BT.709 の線形な値を BT.2020 の線形な値にする。
+pub fn bt709_to_bt2020(v:(f64,f64,f64)) -> (f64,f64,f64) {
+	mul3(&BT709_TO_BT2020,v)
+}
+
+/// PQ の EOTF の逆 (輝度 cd/m² から信号値へ)
+pub fn pq_encode(l:f64) -> f64 {
+	let y = (l.max(0.0)/10000.0).powf(PQ_M1);
+	((PQ_C1+PQ_C2*y)/(1.0+PQ_C3*y)).powf(PQ_M2)
+}
+
+/// PQ の EOTF (信号値から輝度 cd/m² へ)
+/// 黒より下の信号は符号を反転した輝度とする。
+pub fn pq_decode(e:f64) -> f64 {
+	let p = e.abs().powf(1.0/PQ_M2);
+	let y = ((p-PQ_C1).max(0.0)/(PQ_C2-PQ_C3*p)).powf(1.0/PQ_M1);
+	10000.0*y*e.signum()
+}
+
+/// HLG の OETF (シーンの線形な値 0 から 1 を信号値へ)
+pub fn hlg_encode(e:f64) -> f64 {
+	match e.max(0.0) {
+		e if e<=1.0/12.0 => (3.0*e).sqrt(),
+		e => HLG_A*(12.0*e-HLG_B).ln()+HLG_C
+	}
+}
+
+/// HLG の OETF の逆 (信号値からシーンの線形な値へ)
+/// 黒より下の信号は符号を反転した値とする。
+pub fn hlg_decode(e:f64) -> f64 {
+	match e.abs() {
+		v if v<=0.5 => v*v/3.0*e.signum(),
+		v => (((v-HLG_C)/HLG_A).exp()+HLG_B)/12.0*e.signum()
+	}
+}
+
+fn mul3(m:&[[f64;3];3],v:(f64,f64,f64)) -> (f64,f64,f64) {
+	(
+		m[0][0]*v.0 + m[0][1]*v.1 + m[0][2]*v.2,
+		m[1][0]*v.0 + m[1][1]*v.1 + m[1][2]*v.2,
+		m[2][0]*v.0 + m[2][1]*v.1 + m[2][2]*v.2
+	)
+}
+
+/// CIE XYZ から sRGB の線形な値へ (範囲外の値もそのまま返す)
+pub fn xyz_to_linear(xyz:(f64,f64,f64)) -> (f64,f64,f64) {
+	mul3(&XYZ2RGB,xyz)
+}
+
+/// OKLab から sRGB の線形な値へ
+/// https://bottosson.github.io/posts/oklab/ の行列による。
+fn oklab_to_linear(l:f64,a:f64,b:f64) -> (f64,f64,f64) {
+	let l_ = (l + 0.3963377774*a + 0.2158037573*b).powi(3);
+	let m_ = (l - 0.1055613458*a - 0.0638541728*b).powi(3);
+	let s_ = (l - 0.0894841775*a - 1.2914855480*b).powi(3);
+	(
+		 4.0767416621*l_ - 3.3077115913*m_ + 0.2309699292*s_,
+		-1.2684380046*l_ + 2.6097574011*m_ - 0.3413193965*s_,
+		-0.0041960863*l_ - 0.7034186147*m_ + 1.7076147010*s_
+	)
+}
+
+/// CIE L*a*b* (D65) から CIE XYZ へ
+fn lab_to_xyz(l:f64,a:f64,b:f64) -> (f64,f64,f64) {
+	let fy = (l+16.0)/116.0;
+	let fx = fy + a/500.0;
+	let fz = fy - b/200.0;
+	let finv = |t:f64| if t*t*t>EPSILON { t*t*t } else { (116.0*t-16.0)/KAPPA };
+	let y = if l>KAPPA*EPSILON { fy*fy*fy } else { l/KAPPA };
+	(finv(fx)*WHITE.0,y,finv(fz)*WHITE.2)
+}
+
+/// CIE L*u*v* (D65) から CIE XYZ へ
+fn luv_to_xyz(l:f64,u:f64,v:f64) -> (f64,f64,f64) {
+	if l<=0.0 { return (0.0,0.0,0.0); }
+	let d = WHITE.0 + 15.0*WHITE.1 + 3.0*WHITE.2;
+	let (ur,vr) = (4.0*WHITE.0/d,9.0*WHITE.1/d);
+	let up = u/(13.0*l) + ur;
+	let vp = v/(13.0*l) + vr;
+	let y = if l>KAPPA*EPSILON { ((l+16.0)/116.0).powi(3) } else { l/KAPPA };
+	let x = y*9.0*up/(4.0*vp);
+	let z = y*(12.0-3.0*up-20.0*vp)/(4.0*vp);
+	(x,y,z)
+}
+
+/// 明度 l、色相 h (度) の L*u*v* で sRGB に収まる最大の彩度
+/// sRGB の各成分が 0 または 1 になる境界を LCh(uv) の平面上の直線として求め、色相の方向で最も近いものを取る。
+fn max_chroma(l:f64,h:f64) -> f64 {
+
+	let sub1 = (l+16.0).powi(3)/1560896.0;
+	let sub2 = if sub1>EPSILON { sub1 } else { l/KAPPA };
+	let (sin,cos) = h.to_radians().sin_cos();
+
+	let mut chroma = f64::MAX;
+	for m in XYZ2RGB.iter() {
+		for t in [0.0,1.0] {
+			let top1 = (284517.0*m[0] - 94839.0*m[2])*sub2;
+			let top2 = (838422.0*m[2] + 769860.0*m[1] + 731718.0*m[0])*l*sub2 - 769860.0*t*l;
+			let bottom = (632260.0*m[2] - 126452.0*m[1])*sub2 + 126452.0*t;
+			let length = (top2/bottom)/(sin - top1/bottom*cos);
+			if length>=0.0 { chroma = chroma.min(length); }
+		}
+	}
+
+	if chroma==f64::MAX { 0.0 } else { chroma }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	fn close(a:(f64,f64,f64),b:(f64,f64,f64),tolerance:f64) -> bool {
+		(a.0-b.0).abs()<tolerance && (a.1-b.1).abs()<tolerance && (a.2-b.2).abs()<tolerance
+	}
+
+	#[test]
+	fn oklch_reference() {
+		// CSS Color 4 による sRGB の原色の OKLCH (彩度は 0.4 を 100% とする)
+		let (red,_) = lch_to_srgb(CSP::Oklch,62.79553606145516,0.2576833077361567/0.4*100.0,29.233885192342633);
+		assert!(close(red,(1.0,0.0,0.0),1e-6));
+		let (blue,_) = lch_to_srgb(CSP::Oklch,45.20137183853429,0.31321437166460114/0.4*100.0,264.052020638055);
+		assert!(close(blue,(0.0,0.0,1.0),1e-6));
+		let (white,in_gamut) = lch_to_srgb(CSP::Oklch,100.0,0.0,0.0);
+		assert!(close(white,(1.0,1.0,1.0),1e-9) && in_gamut);
+	}
+
+	#[test]
+	fn cielch_white_and_red() {
+		for h in [0.0,120.0,300.0] {
+			let (white,in_gamut) = lch_to_srgb(CSP::Lch,100.0,0.0,h);
+			assert!(close(white,(1.0,1.0,1.0),1e-9) && in_gamut);
+		}
+		let (red,_) = lch_to_srgb(CSP::Lch,53.23711559542937,104.55001152926587/150.0*100.0,39.99986515439812);
+		assert!(close(red,(1.0,0.0,0.0),1e-6));
+		assert!(!lch_to_srgb(CSP::Lch,50.0,100.0,0.0).1);
+	}
+
+	#[test]
+	fn hsluv_max_chroma() {
+		// hsluv の参照実装で彩度 100% となる sRGB の原色の LCh(uv)
+		let primaries = [
+			((1.0,0.0,0.0),53.23711559542937,179.03809692362097,12.177050630061151),
+			((0.0,1.0,0.0),87.73551910966002,135.78953199666867,127.71501294924308),
+			((0.0,0.0,1.0),32.30087290398018,130.68975298582814,265.87432021817733)
+		];
+		for (rgb,l,c,h) in primaries {
+			assert!((max_chroma(l,h)-c).abs()<1e-6*c);
+			let (v,in_gamut) = lch_to_srgb(CSP::Hsluv,l,100.0,h);
+			assert!(close(v,rgb,1e-6) && in_gamut);
+		}
+		assert_eq!(max_chroma(0.0,30.0),0.0);
+		assert!(lch_to_srgb(CSP::Hsluv,DEFAULT_LIGHTNESS,100.0,200.0).1);
+	}
+
+	#[test]
+	fn pq_and_hlg_reference() {
+		assert!((pq_encode(100.0)-0.5081).abs()<1e-4);
+		assert!((pq_encode(203.0)-0.5807).abs()<1e-4);
+		assert!((pq_encode(10000.0)-1.0).abs()<1e-12);
+		assert!((hlg_encode(0.5)-0.8717).abs()<1e-4);
+		assert!((hlg_encode(1.0/12.0)-0.5).abs()<1e-12);
+		assert!((hlg_encode(1.0)-1.0).abs()<1e-6);
+		for l in [0.01,1.0,100.0,203.0,1000.0,10000.0] {
+			assert!((pq_decode(pq_encode(l))-l).abs()<1e-9*l.max(1.0));
+		}
+		for e in [0.0,0.01,1.0/12.0,0.26,0.5,1.0] {
+			assert!((hlg_decode(hlg_encode(e))-e).abs()<1e-12);
+		}
+		assert!(pq_decode(-0.1)<0.0 && hlg_decode(-0.1)<0.0);
+	}
+
+	#[test]
+	fn bt709_to_bt2020_round_trip() {
+		// ITU-R BT.2087 の BT.2020 から BT.709 への行列で元に戻る
+		let inverse = [
+			[ 1.6605,-0.5876,-0.0728],
+			[-0.1246, 1.1329,-0.0083],
+			[-0.0182,-0.1006, 1.1187]
+		];
+		assert!(close(bt709_to_bt2020((1.0,1.0,1.0)),(1.0,1.0,1.0),1e-12));
+		for v in [(1.0,0.0,0.0),(0.0,1.0,0.0),(0.0,0.0,1.0),(0.75,0.75,0.0),(0.2,0.5,0.9)] {
+			assert!(close(mul3(&inverse,bt709_to_bt2020(v)),v,1e-3));
+		}
+		assert!(close(bt709_to_bt2020((1.0,0.0,0.0)),(0.6274,0.0691,0.0164),1e-12));
+	}
+
+}
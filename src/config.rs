@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::env::var as getenv;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use serde::{Deserialize,de::DeserializeOwned};
+
+use crate::{
+	data::*,
+	message::Msg
+};
+
+/// 設定ファイルの [preset.NAME] に記述された名前付きのプリセット
+/// トップレベルの既定値は ConfigOptions として別に読み込む。
+#[derive(Deserialize,Default)]
+#[serde(default)]
+struct PresetFile {
+	preset: HashMap<String,ConfigOptions>
+}
+
+/// 設定ファイルで指定できるオプション
+/// 指定されなかった項目は None のままになる。
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct ConfigOptions {
+	pub mode:     Option<ModeName>,
+	pub terminal: Option<TM>,
+	pub color:    Option<TC>,
+	pub pixels:   Option<TP>,
+	pub aa:       Option<u8>,
+	pub linear_aa: Option<bool>,
+	pub aa_pattern: Option<AP>,
+	pub aa_filter: Option<AF>,
+	pub adaptive_aa: Option<bool>,
+	pub aa_threshold: Option<f64>,
+	pub width:    Option<u16>,
+	pub height:   Option<u16>,
+	pub shot_scale: Option<u16>,
+	pub hud:      Option<bool>,
+	pub threads:  Option<usize>,
+	pub kernel:   Option<KN>,
+	pub precision: Option<PR>,
+	pub palette:  Option<String>,
+	pub palette_offset: Option<f64>,
+	pub palette_repeat: Option<f64>,
+	pub ansi_scheme: Option<String>,
+	pub levels:   Option<VL>,
+	pub depth:    Option<u8>,
+	pub hue:      RotateOptions,
+	pub conic:    RotateOptions,
+	pub bvw:      OffsetOptions,
+	pub bv:       OffsetOptions,
+	pub smpte:    SmpteOptions,
+	pub arib:     RepeatOptions,
+	pub ebu:      EbuOptions,
+	pub hdr:      HdrOptions,
+	pub mandelbrot: MandelbrotOptions,
+	pub newton:   NewtonOptions,
+	pub cie:      CieOptions
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct CieOptions {
+	pub uv:    Option<bool>,
+	pub white: Option<WP>
+}
+
+// 不明な項目を検出できるように、色空間と明度・彩度は flatten せずに各表に並べる
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct RotateOptions {
+	pub rotate:    Option<f64>,
+	pub space:     Option<CSP>,
+	pub lightness: Option<f64>,
+	pub chroma:    Option<f64>
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct OffsetOptions {
+	pub offset:    Option<f64>,
+	pub space:     Option<CSP>,
+	pub lightness: Option<f64>,
+	pub chroma:    Option<f64>
+}
+
+/// 色相環・色相図の色空間と、固定する明度・彩度
+pub struct WheelOptions {
+	pub space:     Option<CSP>,
+	pub lightness: Option<f64>,
+	pub chroma:    Option<f64>
+}
+
+impl RotateOptions {
+	pub fn wheel(&self) -> WheelOptions {
+		WheelOptions { space: self.space, lightness: self.lightness, chroma: self.chroma }
+	}
+	fn or(self,base:RotateOptions) -> RotateOptions {
+		RotateOptions {
+			rotate:    self.rotate.or(base.rotate),
+			space:     self.space.or(base.space),
+			lightness: self.lightness.or(base.lightness),
+			chroma:    self.chroma.or(base.chroma)
+		}
+	}
+}
+
+impl OffsetOptions {
+	pub fn wheel(&self) -> WheelOptions {
+		WheelOptions { space: self.space, lightness: self.lightness, chroma: self.chroma }
+	}
+	fn or(self,base:OffsetOptions) -> OffsetOptions {
+		OffsetOptions {
+			offset:    self.offset.or(base.offset),
+			space:     self.space.or(base.space),
+			lightness: self.lightness.or(base.lightness),
+			chroma:    self.chroma.or(base.chroma)
+		}
+	}
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct RepeatOptions {
+	pub repeat: Option<bool>
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct SmpteOptions {
+	pub repeat: Option<bool>,
+	pub hd:     Option<bool>,
+	pub patch:  Option<HdPatch>
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct EbuOptions {
+	pub repeat: Option<bool>,
+	pub full:   Option<bool>,
+	pub split:  Option<bool>
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct HdrOptions {
+	pub repeat:   Option<bool>,
+	pub transfer: Option<Transfer>
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct MandelbrotOptions {
+	pub re:   Option<String>,
+	pub im:   Option<String>,
+	pub zoom: Option<f64>,
+	pub max:  Option<usize>,
+	pub distance: Option<bool>
+}
+
+#[derive(Deserialize,Default)]
+#[serde(default)]
+pub struct NewtonOptions {
+	pub p:     Option<usize>,
+	pub tau:   Option<f64>,
+	pub max:   Option<usize>,
+	pub speed: Option<bool>
+}
+
+impl ConfigOptions {
+	/// 自身で指定されていない項目を base の値で補う。
+	fn or(self,base:ConfigOptions) -> ConfigOptions {
+		ConfigOptions {
+			mode:     self.mode.or(base.mode),
+			terminal: self.terminal.or(base.terminal),
+			color:    self.color.or(base.color),
+			pixels:   self.pixels.or(base.pixels),
+			aa:       self.aa.or(base.aa),
+			linear_aa: self.linear_aa.or(base.linear_aa),
+			aa_pattern: self.aa_pattern.or(base.aa_pattern),
+			aa_filter: self.aa_filter.or(base.aa_filter),
+			adaptive_aa: self.adaptive_aa.or(base.adaptive_aa),
+			aa_threshold: self.aa_threshold.or(base.aa_threshold),
+			width:    self.width.or(base.width),
+			height:   self.height.or(base.height),
+			shot_scale: self.shot_scale.or(base.shot_scale),
+			hud:      self.hud.or(base.hud),
+			threads:  self.threads.or(base.threads),
+			kernel:   self.kernel.or(base.kernel),
+			precision: self.precision.or(base.precision),
+			palette:  self.palette.or(base.palette),
+			palette_offset: self.palette_offset.or(base.palette_offset),
+			palette_repeat: self.palette_repeat.or(base.palette_repeat),
+			ansi_scheme: self.ansi_scheme.or(base.ansi_scheme),
+			levels:   self.levels.or(base.levels),
+			depth:    self.depth.or(base.depth),
+			hue:   self.hue.or(base.hue),
+			conic: self.conic.or(base.conic),
+			bvw:   self.bvw.or(base.bvw),
+			bv:    self.bv.or(base.bv),
+			smpte: SmpteOptions {
+				repeat: self.smpte.repeat.or(base.smpte.repeat),
+				hd:     self.smpte.hd.or(base.smpte.hd),
+				patch:  self.smpte.patch.or(base.smpte.patch)
+			},
+			arib:  RepeatOptions { repeat: self.arib.repeat.or(base.arib.repeat) },
+			ebu: EbuOptions {
+				repeat: self.ebu.repeat.or(base.ebu.repeat),
+				full:   self.ebu.full.or(base.ebu.full),
+				split:  self.ebu.split.or(base.ebu.split)
+			},
+			hdr: HdrOptions {
+				repeat:   self.hdr.repeat.or(base.hdr.repeat),
+				transfer: self.hdr.transfer.or(base.hdr.transfer)
+			},
+			mandelbrot: MandelbrotOptions {
+				re:   self.mandelbrot.re.or(base.mandelbrot.re),
+				im:   self.mandelbrot.im.or(base.mandelbrot.im),
+				zoom: self.mandelbrot.zoom.or(base.mandelbrot.zoom),
+				max:  self.mandelbrot.max.or(base.mandelbrot.max),
+				distance: self.mandelbrot.distance.or(base.mandelbrot.distance)
+			},
+			newton: NewtonOptions {
+				p:     self.newton.p.or(base.newton.p),
+				tau:   self.newton.tau.or(base.newton.tau),
+				max:   self.newton.max.or(base.newton.max),
+				speed: self.newton.speed.or(base.newton.speed)
+			},
+			cie: CieOptions {
+				uv:    self.cie.uv.or(base.cie.uv),
+				white: self.cie.white.or(base.cie.white)
+			}
+		}
+	}
+}
+
+impl ModeName {
+	/// サブコマンドを省略した場合と同じく、オプションを指定していない状態の描画モードを返す。
+	pub fn draw_mode(&self) -> EDM {
+		match self {
+			ModeName::Ansi       => EDM::Ansi,
+			ModeName::Hue        => EDM::Hue {rotate:None,space:None,lightness:None,chroma:None},
+			ModeName::Conic      => EDM::Conic {rotate:None,space:None,lightness:None,chroma:None},
+			ModeName::Bvw        => EDM::BVW {offset:None,space:None,lightness:None,chroma:None},
+			ModeName::Bv         => EDM::BV {offset:None,space:None,lightness:None,chroma:None},
+			ModeName::Smpte      => EDM::Smpte {repeat:false,no_repeat:false,hd:false,no_hd:false,patch:None},
+			ModeName::Arib       => EDM::Arib {repeat:false,no_repeat:false},
+			ModeName::Mandelbrot => EDM::Mandelbrot {re:None,im:None,zoom:None,max:None,distance:false,no_distance:false},
+			ModeName::Newton     => EDM::Newton {p:None,tau:None,max:None,speed:false,no_speed:false},
+			ModeName::Cie        => EDM::Cie {uv:false,no_uv:false,white:None},
+			ModeName::Ebu        => EDM::Ebu {repeat:false,no_repeat:false,full:false,no_full:false,split:false,no_split:false},
+			ModeName::Hdr        => EDM::Hdr {repeat:false,no_repeat:false,transfer:None}
+		}
+	}
+}
+
+fn config_path() -> Option<PathBuf> {
+	let dir = match (getenv("XDG_CONFIG_HOME"),getenv("HOME")) {
+		(Ok(x),_) if !x.is_empty() => PathBuf::from(x),
+		(_,Ok(h)) if !h.is_empty() => PathBuf::from(h).join(".config"),
+		_ => { return None; }
+	};
+	Some(dir.join("visualizer").join("config.toml"))
+}
+
+/// 設定ファイルを読み込み、指定されたプリセットをトップレベルの既定値に重ねたものを返す。
+/// 設定ファイルが存在しない場合は全て未指定として扱う。開けない場合や不明な項目は警告を表示して無視する。
+pub fn load_config(preset:Option<&str>) -> ConfigOptions {
+
+	let text = match config_path().map(|p| (read_to_string(&p),p)) {
+		Some((Ok(text),_)) => text,
+		Some((Err(e),_)) if e.kind()==ErrorKind::NotFound => String::new(),
+		Some((Err(e),p)) => {
+			eprintln!("{} {}\n{}",Msg::ConfigUnreadable,p.display(),e);
+			String::new()
+		},
+		None => String::new()
+	};
+
+	// 既定値とプリセットを別々に読み込み、それぞれに属さない項目だけを不明な項目とする
+	let defaults:ConfigOptions = parse_config(&text,|key| key!="preset");
+	let mut file:PresetFile = parse_config(&text,|key| key.starts_with("preset."));
+
+	let c = match preset {
+		None => defaults,
+		Some(name) => match file.preset.remove(name) {
+			Some(p) => p.or(defaults),
+			None => error!(format!("{} {}",Msg::PresetNotFound,name))
+		}
+	};
+
+	if let Some(p) = c.newton.p.filter(|p| *p<2) {
+		error!(format!("{} {}",Msg::DegreeInvalid,p));
+	}
+
+	c
+
+}
+
+/// 設定ファイルを T として読み込み、 T に存在しない項目のうち report が真となるものを警告する。
+fn parse_config<T:DeserializeOwned>(text:&str,report:fn(&str) -> bool) -> T {
+	let parsed = toml::Deserializer::parse(text).and_then(|de| serde_ignored::deserialize(de,|path| {
+		let key = path.to_string();
+		if report(&key) { eprintln!("{} {}",Msg::ConfigUnknownKey,key); }
+	}));
+	match parsed {
+		Ok(c) => c,
+		Err(e) => error!(format!("{}\n{}",Msg::ConfigInvalid,e))
+	}
+}
@@ -7,6 +7,7 @@ mod primitive {
 	pub type CF = (f64,f64);
 
 	/// 色を表現する型
+	#[derive(PartialEq)]
 	pub enum C {
 		Float{r:f64,g:f64,b:f64,a:f64},
 		Int{r:u8,g:u8,b:u8,a:u8},
@@ -24,6 +25,7 @@ pub use primitive::*;
 // 共通のデータ型
 mod common {
 
+	use std::sync::atomic::AtomicUsize;
 	use crate::data::primitive::*;
 
 	/// ステータス管理構造体
@@ -34,30 +36,164 @@ mod common {
 		pub color:     TC,
 		pub pixels:    TP,
 		pub aa:        u8,
-		pub output:    Option<String>
+		pub linear_aa: bool,
+		pub aa_pattern: AP,
+		pub aa_filter: AF,
+		/// アダプティブアンチエイリアスの閾値。 None なら全てのピクセルをスーパーサンプリングする。
+		pub aa_threshold: Option<f64>,
+		/// 直前のフレームで計算したフラグメントの数
+		pub samples:   AtomicUsize,
+		pub other_modes: Vec<(DM,u8)>,
+		pub output:    Option<String>,
+		pub shot_scale: u16,
+		pub threads:   usize,
+		pub kernel:    KN,
+		pub precision: PR,
+		pub palette:   Option<PaletteStatus>,
+		pub scheme:    crate::scheme::Scheme,
+		pub levels:    VL,
+		/// PNG に出力する1チャンネルあたりのビット数 (8 または 16)
+		pub depth:     u8,
+		pub hud:       HudStatus
+	}
+
+	/// スカラー値を色に対応させるパレットと、その周期のずらし量、繰り返し回数
+	pub struct PaletteStatus {
+		pub gradient: crate::palette::Gradient,
+		pub offset:   f64,
+		pub repeat:   f64
+	}
+
+	/// ターミナル出力時のオーバーレイ表示の状態
+	pub struct HudStatus {
+		pub visible:    bool,
+		pub help:       bool,
+		pub mouse:      Option<CF>,
+		pub frame_time: f64
 	}
 
 	/// 描画モードとそれぞれのオプション
 	mod draw_mode {
 
+		use std::sync::OnceLock;
+		use clap::ArgEnum;
+		use serde::Deserialize;
 		use crate::data::primitive::*;
+		use crate::bigfixed::BigFixed;
 
 		/// 描画モードの指定
 		pub enum DrawMode {
 			Ansi,
 			Color(ColorStatus),
 			Colorbar(ColorbarStatus),
-			Mandelbrot,
+			Cie(CieStatus),
+			Mandelbrot(MandelbrotStatus),
 			NewtonApprox(NewtonApproxStatus)
 		}
 		pub type DM = DrawMode;
 
+		/// 描画モードを識別するためのキーワード
+		/// 実行中の描画モードの切り替えにおける番号はこの並び順による。
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum ModeName {
+			Ansi,
+			Hue,
+			Conic,
+			Bv,
+			Bvw,
+			Smpte,
+			Arib,
+			Mandelbrot,
+			Newton,
+			Cie,
+			Ebu,
+			Hdr
+		}
+
+		impl ModeName {
+			/// 数字キーの 1-9 と 0 は先頭から10個の描画モードに対応する。
+			pub const ALL:[ModeName;12] = [
+				ModeName::Ansi,
+				ModeName::Hue,
+				ModeName::Conic,
+				ModeName::Bv,
+				ModeName::Bvw,
+				ModeName::Smpte,
+				ModeName::Arib,
+				ModeName::Mandelbrot,
+				ModeName::Newton,
+				ModeName::Cie,
+				ModeName::Ebu,
+				ModeName::Hdr
+			];
+
+			/// サブコマンド名と同じキーワード
+			pub fn keyword(&self) -> &'static str {
+				match self {
+					ModeName::Ansi       => "ansi",
+					ModeName::Hue        => "hue",
+					ModeName::Conic      => "conic",
+					ModeName::Bv         => "bv",
+					ModeName::Bvw        => "bvw",
+					ModeName::Smpte      => "smpte",
+					ModeName::Arib       => "arib",
+					ModeName::Mandelbrot => "mandelbrot",
+					ModeName::Newton     => "newton",
+					ModeName::Cie        => "cie",
+					ModeName::Ebu        => "ebu",
+					ModeName::Hdr        => "hdr"
+				}
+			}
+		}
+
+		impl DrawMode {
+			pub fn name(&self) -> ModeName {
+				match self {
+					DM::Ansi => ModeName::Ansi,
+					DM::Color(cs) => match cs.mode {
+						CSMode::ConicW => ModeName::Hue,
+						CSMode::Conic  => ModeName::Conic,
+						CSMode::BV     => ModeName::Bv,
+						CSMode::BVW    => ModeName::Bvw
+					},
+					DM::Colorbar(cbs) => match cbs.mode {
+						CBSMode::SMPTE|CBSMode::RP219(_) => ModeName::Smpte,
+						CBSMode::ARIB  => ModeName::Arib,
+						CBSMode::EBU{..} => ModeName::Ebu,
+						CBSMode::HDR(_) => ModeName::Hdr
+					},
+					DM::Mandelbrot(_) => ModeName::Mandelbrot,
+					DM::NewtonApprox(_) => ModeName::Newton,
+					DM::Cie(_) => ModeName::Cie
+				}
+			}
+		}
+
+		/// 色相環・色相図の状態
+		/// rgb 以外の色空間では明度 lightness と彩度 chroma を固定し (どちらも %)、 sRGB の範囲外の色を縞模様で示す。
 		pub struct ColorStatus {
 			pub angle: f64,
 			pub mode:  CSMode,
+			pub space: CSP,
+			pub lightness: f64,
+			pub chroma: f64,
 			pub mouse_position: CF
 		}
 
+		/// 色相環・色相図の色空間
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum ColorSpace {
+			Rgb,
+			Oklch,
+			Lch,
+			Hsluv
+		}
+		#[allow(clippy::upper_case_acronyms)]
+		pub type CSP = ColorSpace;
+
+		#[allow(clippy::upper_case_acronyms)]
 		pub enum CSMode {
 			ConicW,
 			Conic,
@@ -69,9 +205,70 @@ mod common {
 			pub mode:   CBSMode,
 			pub repeat: bool
 		}
+		#[allow(clippy::upper_case_acronyms)]
 		pub enum CBSMode {
 			SMPTE,
-			ARIB
+			/// SMPTE RP 219 の HD カラーバー。 Pattern 2 の左から2番目 (*1) のパッチを指定する。
+			RP219(HdPatch),
+			ARIB,
+			/// EBU カラーバー。 full なら 100/0/100/0、そうでなければ 100/0/75/0 とする。
+			/// split なら下半分を各バーと同じ輝度の灰色にする (BBC のスプリットフィールド)。
+			EBU{full:bool,split:bool},
+			/// ITU-R BT.2111 の HDR のカラーバー
+			HDR(Transfer)
+		}
+
+		/// HDR の伝達関数
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum Transfer {
+			Pq,
+			Hlg
+		}
+
+		/// RP 219 の Pattern 2 の *1 のパッチ
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="kebab-case")]
+		pub enum HdPatch {
+			PlusI,
+			MinusI,
+			White75,
+			White100
+		}
+
+		/// 色度図の状態
+		/// uv が true なら CIE 1976 u'v'、 false なら CIE 1931 xy の色度図とする。
+		pub struct CieStatus {
+			pub uv:    bool,
+			pub white: WP
+		}
+
+		/// 色度図に印を付ける白色点
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum WhitePoint {
+			D65,
+			D50,
+			D55,
+			D75,
+			A,
+			C,
+			E,
+			Dci
+		}
+		pub type WP = WhitePoint;
+
+		/// マンデルブロ集合の表示範囲
+		/// 中心は拡大率に応じた精度の多倍長の固定小数点数で保持し、 center にはその f64 での近似値を置く。
+		/// 拡大率が大きい場合は中心の参照軌道を多倍長で計算し、 orbit に保存して各点の摂動の計算に使う。
+		pub struct MandelbrotStatus {
+			pub re:     BigFixed,
+			pub im:     BigFixed,
+			pub center: CF,
+			pub zoom:   f64,
+			pub max:    usize,
+			pub distance: bool,
+			pub orbit:  OnceLock<Vec<CF>>
 		}
 
 		pub struct NewtonApproxStatus {
@@ -88,15 +285,18 @@ mod common {
 	mod common_options {
 
 		use clap::ArgEnum;
+		use serde::Deserialize;
 
-		#[derive(ArgEnum,Clone,Copy)]
+		#[derive(ArgEnum,Deserialize,Clone,Copy)]
+		#[serde(rename_all="lowercase")]
 		pub enum TerminalMode {
 			Texts,
 			Image
 		}
 		pub type TM = TerminalMode;
 
-		#[derive(ArgEnum,Clone,Copy)]
+		#[derive(ArgEnum,Deserialize,Clone,Copy)]
+		#[serde(rename_all="lowercase")]
 		pub enum TerminalColor {
 			Auto,
 			Less,
@@ -104,13 +304,96 @@ mod common {
 		}
 		pub type TC = TerminalColor;
 
-		#[derive(ArgEnum,Clone,Copy)]
+		#[derive(ArgEnum,Deserialize,Clone,Copy)]
+		#[serde(rename_all="lowercase")]
 		pub enum TerminalPixels {
 			Single,
 			Double,
 		}
 		pub type TP = TerminalPixels;
 
+		/// アンチエイリアスのサンプルの配置
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum AaPattern {
+			Grid,
+			Rotated,
+			Jitter,
+			Halton,
+			Sobol,
+			Poisson
+		}
+		pub type AP = AaPattern;
+
+		/// アンチエイリアスのサンプルを重み付けする再構成フィルタ
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum AaFilter {
+			Box,
+			Tent,
+			Gaussian,
+			Lanczos
+		}
+		pub type AF = AaFilter;
+
+		/// フラクタルの計算方法
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum Kernel {
+			Scalar,
+			Simd
+		}
+		pub type KN = Kernel;
+
+		impl Kernel {
+			/// --kernel で指定するキーワード
+			pub fn keyword(&self) -> &'static str {
+				match self {
+					Kernel::Scalar => "scalar",
+					Kernel::Simd   => "simd"
+				}
+			}
+		}
+
+		/// フラクタルの計算に使う浮動小数点数の精度
+		/// diff は f64 と f32 の両方で計算し、結果が異なる点を強調する。
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum Precision {
+			F32,
+			F64,
+			Diff
+		}
+		pub type PR = Precision;
+
+		impl Precision {
+			/// --precision で指定するキーワード
+			pub fn keyword(&self) -> &'static str {
+				match self {
+					Precision::F32  => "f32",
+					Precision::F64  => "f64",
+					Precision::Diff => "diff"
+				}
+			}
+		}
+
+		/// カラーバーの信号レベルを画素値に対応させる範囲
+		/// full は黒を 0、白を 1 とし、 limited は黒を 16/255、白を 235/255 として黒より下と白より上の信号レベルも残す。
+		#[derive(ArgEnum,Deserialize,Clone,Copy,PartialEq)]
+		#[serde(rename_all="lowercase")]
+		pub enum VideoLevels {
+			Full,
+			Limited
+		}
+		pub type VL = VideoLevels;
+
+		#[derive(ArgEnum,Clone,Copy)]
+		pub enum Language {
+			Ja,
+			En
+		}
+		pub type LG = Language;
+
 	}
 	pub use common_options::*;
 
@@ -143,84 +426,294 @@ mod external {
 		/// 画像ファイルの縦幅を指定します。ターミナルモードでは無視されます。
 		#[clap(long)]
 		pub height: Option<u16>,
-		/// ターミナルに出力する場合の出力形式 (テキスト/画像) を指定します。画像は一部のターミナルでのみ対応しています。
-		#[clap(short,long,arg_enum,default_value_t=TM::Texts)]
-		pub terminal: TM,
-		/// ターミナルへのテキスト出力に使用する色数を選択します。 auto はターミナルがフルカラーに対応していればフルカラーにします。 less, full はターミナルの対応如何に依らず強制的に設定を適用します。
-		#[clap(long,arg_enum,default_value_t=TC::Auto)]
-		pub color: TC,
-		/// ターミナルへのテキスト出力の解像度を選択します。ターミナルによっては double の解像度で適切に表示されません。
-		#[clap(long,arg_enum,default_value_t=TP::Single)]
-		pub pixels: TP,
-		/// アンチエイリアスのレベルを指定します。2以上の整数を指定するとアンチエイリアスが有効になります。ANSIテストとカラーバー以外で有効なオプションです。
-		#[clap(long,default_value_t=2)]
-		pub aa: u8,
+		/// ターミナルに出力する場合の出力形式 (テキスト/画像) を指定します。画像は一部のターミナルでのみ対応しています。既定値は texts です。
+		#[clap(short,long,arg_enum)]
+		pub terminal: Option<TM>,
+		/// ターミナルへのテキスト出力に使用する色数を選択します。 auto はターミナルがフルカラーに対応していればフルカラーにします。 less, full はターミナルの対応如何に依らず強制的に設定を適用します。既定値は auto です。
+		#[clap(long,arg_enum)]
+		pub color: Option<TC>,
+		/// ターミナルへのテキスト出力の解像度を選択します。ターミナルによっては double の解像度で適切に表示されません。既定値は single です。
+		#[clap(long,arg_enum)]
+		pub pixels: Option<TP>,
+		/// アンチエイリアスのレベルを指定します。2以上の整数を指定するとアンチエイリアスが有効になります。サブピクセルが全て同じ端末の色 (ANSIカラーなど) のピクセルは、平均せずにその色のまま出力します。既定値は 2 で、境目が混ざらないように ansi とカラーバーでは 0 です。
 		#[clap(long)]
-		/// visualizer コマンドの使い方を表示します。
-		pub help: bool
+		pub aa: Option<u8>,
+		/// アンチエイリアスでサブピクセルの色を sRGB の符号化を戻した線形な光の量で平均するかどうかを true, false で指定します。 false にすると符号化された値のまま平均する以前の描画になり、境界が暗くなります。既定値は true です。
+		#[clap(long)]
+		pub linear_aa: Option<bool>,
+		/// アンチエイリアスのサンプルの配置を指定します。 grid は格子、 rotated は各行・各列に1つずつ入るよう傾けた格子、 jitter は格子の各区画内で固定の種により乱数でずらした配置、 halton と sobol は低食い違い量列、 poisson はポアソンディスクです。既定値は grid です。
+		#[clap(long,arg_enum)]
+		pub aa_pattern: Option<AP>,
+		/// アンチエイリアスのサンプルを重み付けする再構成フィルタを指定します。 box はピクセル内の単純な平均、 tent, gaussian, lanczos はピクセルの境界を越えた周囲のサンプルも重み付けして使います。範囲が広いフィルタほどサンプル数が増えます。既定値は box です。
+		#[clap(long,arg_enum)]
+		pub aa_filter: Option<AF>,
+		/// アダプティブアンチエイリアスを有効にします。各ピクセルの中心を1回だけ計算し、上下左右のピクセルとの色の差が閾値を超えるピクセルだけをスーパーサンプリングします。
+		#[clap(long,overrides_with="no-adaptive-aa")]
+		pub adaptive_aa: bool,
+		/// 設定ファイルやプリセットで有効にしたアダプティブアンチエイリアスを無効にします。
+		#[clap(long,overrides_with="adaptive-aa")]
+		pub no_adaptive_aa: bool,
+		/// アダプティブアンチエイリアスでスーパーサンプリングする色の差の閾値を、 RGBA の各成分の 0 から 1 の差で指定します。既定値は 0.02 です。
+		#[clap(long)]
+		pub aa_threshold: Option<f64>,
+		/// ターミナルモードで s キーにより保存するスクリーンショットの倍率を指定します。画面の1ピクセルあたりの画像のピクセル数です。既定値は 8 です。
+		#[clap(long)]
+		pub shot_scale: Option<u16>,
+		/// ターミナルモードで画面の最下行に描画モード、パラメータ、マウス位置、描画時間、色数を表示します。 h キーで表示を切り替えられます。
+		#[clap(long,overrides_with="no-hud")]
+		pub hud: bool,
+		/// 設定ファイルやプリセットで有効にした HUD を起動時に表示しません。
+		#[clap(long,overrides_with="hud")]
+		pub no_hud: bool,
+		/// 描画に使用するスレッド数を指定します。指定しない場合は CPU の論理コア数になります。
+		#[clap(long)]
+		pub threads: Option<usize>,
+		/// マンデルブロ集合とニュートン近似の計算方法を指定します。 simd は複数の点をまとめて計算します。どちらも同じ結果になります。既定値は simd です。
+		#[clap(long,arg_enum)]
+		pub kernel: Option<KN>,
+		/// マンデルブロ集合とニュートン近似の色に使うパレットを指定します。組み込みのパレット (viridis, magma, inferno, cividis, twilight)、または GIMP のグラデーションファイル (.ggr) か16進数の色を並べたファイルのパスを指定します。指定しない場合は色相により描画します。
+		#[clap(long)]
+		pub palette: Option<String>,
+		/// パレットの周期をずらす量を 0 から 1 の割合で指定します。既定値は 0 です。
+		#[clap(long,allow_hyphen_values=true)]
+		pub palette_offset: Option<f64>,
+		/// パレットを繰り返す回数を指定します。既定値は 1 です。
+		#[clap(long)]
+		pub palette_repeat: Option<f64>,
+		/// ANSIテストの色を画像に出力するときなどに使う端末の配色を指定します。組み込みの配色 (xterm, vga, solarized, tango)、または iTerm2 の配色ファイル (.itermcolors) か Windows Terminal の配色の JSON ファイルのパスを指定します。既定値は xterm です。
+		#[clap(long)]
+		pub ansi_scheme: Option<String>,
+		/// カラーバーの信号レベルを画素値に対応させる範囲を指定します。 full は黒を 0、白を 255 とします。 limited は黒を 16、白を 235 とし、 PLUGE の黒より下の段や白より上の信号も画素値に残します。既定値は full です。
+		#[clap(long,arg_enum)]
+		pub levels: Option<VL>,
+		/// PNG に出力する1チャンネルあたりのビット数を 8 または 16 で指定します。 16 では 8bit で丸められる信号レベルの差も残ります。出力先の拡張子が .yuv の場合は 10bit の Y'CbCr 4:4:4 (yuv444p10le) の生データを、 .exr の場合は線形な値の浮動小数点数の OpenEXR を出力します。 hdr の PNG には cICP チャンクを付けます。既定値は hdr で 16、それ以外で 8 です。
+		#[clap(long)]
+		pub depth: Option<u8>,
+		/// マンデルブロ集合とニュートン近似の計算に使う浮動小数点数の精度を指定します。 diff は f64 と f32 の両方で計算し、結果が異なる点を白で、一致する点を暗く描画します。既定値は f64 です。
+		#[clap(long,arg_enum)]
+		pub precision: Option<PR>,
+		/// 設定ファイル ($XDG_CONFIG_HOME/visualizer/config.toml) に定義されたプリセットを適用します。コマンドラインでの指定はプリセットより優先されます。
+		#[clap(long)]
+		pub preset: Option<String>,
+		/// メッセージとヘルプの言語を指定します。指定しない場合は環境変数 LC_ALL, LC_MESSAGES, LANG から判定します。
+		#[clap(long,arg_enum)]
+		pub lang: Option<LG>
 	}
 
-	#[derive(Subcommand)]
+	#[derive(Subcommand,Clone)]
+	#[allow(clippy::upper_case_acronyms)]
 	pub enum DrawMode {
 		/// ターミナルのANSIカラー表示をテストします。
 		Ansi,
 		/// 中心が白の色相環を描画します。
 		Hue {
-			#[clap(short,long,default_value_t = 0.0)]
-			/// 色相環を回転させる角度を度数法により指定します。
-			rotate: f64
+			#[clap(short,long)]
+			/// 色相環を回転させる角度を度数法により指定します。既定値は 0 です。
+			rotate: Option<f64>,
+			#[clap(long,arg_enum)]
+			/// 色空間を指定します。 rgb 以外では明度と彩度を固定した色相を描画し、 sRGB の範囲外の色を縞模様で示します。既定値は rgb です。
+			space: Option<CSP>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の明度を 0 から 100 の % で指定します。既定値は 70 です。
+			lightness: Option<f64>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の彩度を % で指定します。 100% は OKLCH で 0.4、 CIELCh で 150、 HSLuv では sRGB に収まる最大の彩度です。既定値は hsluv で 100、それ以外で 30 です。
+			chroma: Option<f64>
 		},
 		/// 色相環を描画します。
 		Conic {
-			#[clap(short,long,default_value_t = 0.0)]
-			/// 色相環を回転させる角度を度数法により指定します。
-			rotate: f64
+			#[clap(short,long)]
+			/// 色相環を回転させる角度を度数法により指定します。既定値は 0 です。
+			rotate: Option<f64>,
+			#[clap(long,arg_enum)]
+			/// 色空間を指定します。 rgb 以外では明度と彩度を固定した色相を描画し、 sRGB の範囲外の色を縞模様で示します。既定値は rgb です。
+			space: Option<CSP>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の明度を 0 から 100 の % で指定します。既定値は 70 です。
+			lightness: Option<f64>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の彩度を % で指定します。 100% は OKLCH で 0.4、 CIELCh で 150、 HSLuv では sRGB に収まる最大の彩度です。既定値は hsluv で 100、それ以外で 30 です。
+			chroma: Option<f64>
 		},
 		/// 横方向を色相、縦方向をHSL明度として描画します。
 		BVW {
-			#[clap(short,long,default_value_t = 0.0)]
-			/// 図を横方向でずらす際の色相角度を度数法により指定します。
-			offset: f64
+			#[clap(short,long)]
+			/// 図を横方向でずらす際の色相角度を度数法により指定します。既定値は 0 です。
+			offset: Option<f64>,
+			#[clap(long,arg_enum)]
+			/// 色空間を指定します。 rgb 以外では明度と彩度を固定した色相を描画し、 sRGB の範囲外の色を縞模様で示します。既定値は rgb です。
+			space: Option<CSP>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の明度を 0 から 100 の % で指定します。既定値は 70 です。
+			lightness: Option<f64>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の彩度を % で指定します。 100% は OKLCH で 0.4、 CIELCh で 150、 HSLuv では sRGB に収まる最大の彩度です。既定値は hsluv で 100、それ以外で 30 です。
+			chroma: Option<f64>
 		},
 		/// 横方向を色相、縦方向をHSV明度として描画します。
 		BV {
-			#[clap(short,long,default_value_t = 0.0)]
-			/// 図を横方向でずらす際の色相角度を度数法により指定します。
-			offset: f64
+			#[clap(short,long)]
+			/// 図を横方向でずらす際の色相角度を度数法により指定します。既定値は 0 です。
+			offset: Option<f64>,
+			#[clap(long,arg_enum)]
+			/// 色空間を指定します。 rgb 以外では明度と彩度を固定した色相を描画し、 sRGB の範囲外の色を縞模様で示します。既定値は rgb です。
+			space: Option<CSP>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の明度を 0 から 100 の % で指定します。既定値は 70 です。
+			lightness: Option<f64>,
+			#[clap(long)]
+			/// 色空間が rgb 以外の場合の彩度を % で指定します。 100% は OKLCH で 0.4、 CIELCh で 150、 HSLuv では sRGB に収まる最大の彩度です。既定値は hsluv で 100、それ以外で 30 です。
+			chroma: Option<f64>
 		},
-		/// アナログ放送用のSMPTEカラーバーを描画します。
+		/// アナログ放送用のSMPTEカラーバーを描画します。 --hd で SMPTE RP 219 の HD カラーバーになります。
 		Smpte {
-			#[clap(short,long)]
+			#[clap(short,long,overrides_with="no-repeat")]
 			/// 図を繰り返します。
-			repeat: bool
+			repeat: bool,
+			#[clap(long,overrides_with="repeat")]
+			/// 設定ファイルやプリセットの repeat を打ち消し、図を繰り返しません。
+			no_repeat: bool,
+			#[clap(long,overrides_with="no-hd")]
+			/// 16:9 の SMPTE RP 219 の HD カラーバー (+I, -I, PLUGE 付き) を描画します。
+			hd: bool,
+			#[clap(long,overrides_with="hd")]
+			/// 設定ファイルやプリセットの hd を打ち消し、アナログ放送用のカラーバーを描画します。
+			no_hd: bool,
+			#[clap(long,arg_enum)]
+			/// --hd の場合に Pattern 2 の左から2番目のパッチを指定します。既定値は plus-i です。
+			patch: Option<HdPatch>
 		},
 		/// マンデルブロ集合を描画します。
-		Mandelbrot,
+		Mandelbrot {
+			#[clap(long,allow_hyphen_values=true)]
+			/// 表示範囲の中心の実部を10進数で指定します。 f64 を超える桁数も使用できます。既定値は -0.5 です。
+			re: Option<String>,
+			#[clap(long,allow_hyphen_values=true)]
+			/// 表示範囲の中心の虚部を10進数で指定します。 f64 を超える桁数も使用できます。既定値は 0 です。
+			im: Option<String>,
+			#[clap(short,long)]
+			/// 拡大率を指定します。 1e12 以上では多倍長で計算した参照軌道からの摂動により描画します。既定値は 1 です。
+			zoom: Option<f64>,
+			#[clap(short,long)]
+			/// 最大イテレート回数を指定します。この回数を超えても発散しない場合は黒色になります。既定値は 200 です。
+			max: Option<usize>,
+			#[clap(short,long,overrides_with="no-distance")]
+			/// イテレート回数の代わりに、境界までの推定距離により濃淡を付けます。境界の近くほど暗くなります。
+			distance: bool,
+			#[clap(long,overrides_with="distance")]
+			/// 設定ファイルやプリセットの distance を打ち消し、イテレート回数により描画します。
+			no_distance: bool
+		},
 		/// 1の p 乗根のニュートン近似の収束先の偏角を色相として描画します。
 		Newton {
-			#[clap(short,default_value_t = 6)]
-			/// p 乗根の次数を指定します。
-			p: usize,
-			#[clap(short,long,default_value_t = 0.1)]
-			/// 収束因子 τ を指定します。
-			tau: f64,
-			#[clap(short,long,default_value_t = 1000)]
-			/// 最大イテレート回数を指定します。この回数を超えても収束しない場合は黒色になります。
-			max: usize,
+			#[clap(short)]
+			/// p 乗根の次数を指定します。既定値は 6 です。
+			p: Option<usize>,
+			#[clap(short,long)]
+			/// 収束因子 τ を指定します。既定値は 0.1 です。
+			tau: Option<f64>,
 			#[clap(short,long)]
+			/// 最大イテレート回数を指定します。この回数を超えても収束しない場合は黒色になります。既定値は 1000 です。
+			max: Option<usize>,
+			#[clap(short,long,overrides_with="no-speed")]
 			/// 収束までに要した時間により色分けします。収束までより時間を要した点ほど暗い値になります。
-			speed: bool
+			speed: bool,
+			#[clap(long,overrides_with="speed")]
+			/// 設定ファイルやプリセットの speed を打ち消し、収束先の偏角により描画します。
+			no_speed: bool
 		},
 		/// デジタル放送用ARIBカラーバーを描画します。
 		Arib {
-			#[clap(short,long)]
+			#[clap(short,long,overrides_with="no-repeat")]
+			/// 図を繰り返します。
+			repeat: bool,
+			#[clap(long,overrides_with="repeat")]
+			/// 設定ファイルやプリセットの repeat を打ち消し、図を繰り返しません。
+			no_repeat: bool
+		},
+		/// EBU カラーバー (100/0/75/0) を描画します。
+		Ebu {
+			#[clap(short,long,overrides_with="no-repeat")]
+			/// 図を繰り返します。
+			repeat: bool,
+			#[clap(long,overrides_with="repeat")]
+			/// 設定ファイルやプリセットの repeat を打ち消し、図を繰り返しません。
+			no_repeat: bool,
+			#[clap(short,long,overrides_with="no-full")]
+			/// 100/0/100/0 のカラーバーにします。
+			full: bool,
+			#[clap(long,overrides_with="full")]
+			/// 設定ファイルやプリセットの full を打ち消し、 100/0/75/0 のカラーバーにします。
+			no_full: bool,
+			#[clap(short,long,overrides_with="no-split")]
+			/// 下半分を各バーと同じ輝度の灰色にした BBC のスプリットフィールドのカラーバーにします。
+			split: bool,
+			#[clap(long,overrides_with="split")]
+			/// 設定ファイルやプリセットの split を打ち消します。
+			no_split: bool
+		},
+		/// ITU-R BT.2111 の HDR のカラーバーを描画します。 --levels で狭い範囲 (limited) と全範囲 (full) を選びます。
+		Hdr {
+			#[clap(short,long,overrides_with="no-repeat")]
 			/// 図を繰り返します。
-			repeat: bool
+			repeat: bool,
+			#[clap(long,overrides_with="repeat")]
+			/// 設定ファイルやプリセットの repeat を打ち消し、図を繰り返しません。
+			no_repeat: bool,
+			#[clap(long,arg_enum)]
+			/// 伝達関数を pq (SMPTE ST 2084) または hlg で指定します。既定値は pq です。
+			transfer: Option<Transfer>
+		},
+		/// CIE 1931 xy 色度図を、表示できる最も近い色で塗って描画します。 sRGB (実線)、 Display P3 (破線)、 Rec.2020 (点線)、 Adobe RGB (一点鎖線) の色域の三角形と白色点の印を重ねます。
+		Cie {
+			#[clap(short,long,overrides_with="no-uv")]
+			/// CIE 1976 u'v' 色度図として描画します。
+			uv: bool,
+			#[clap(long,overrides_with="uv")]
+			/// 設定ファイルやプリセットの uv を打ち消し、 CIE 1931 xy 色度図として描画します。
+			no_uv: bool,
+			#[clap(short,long,arg_enum)]
+			/// 印を付ける白色点を指定します。 dci は DCI-P3 の白色点です。既定値は d65 です。
+			white: Option<WP>
+		},
+		/// 端末の実際の色を OSC 4 (パレットの 0-255)、 OSC 10 (文字色)、 OSC 11 (背景色) で問い合わせ、 xterm の既定値と並べて表示します。異なる色には * を付けます。 16-255 は異なる色だけを表示します。
+		Colors {
+			#[clap(long)]
+			/// 結果を JSON で出力します。 Windows Terminal の配色と同じ名前を使うため、文字色、背景色と 0-15 の全てに応答があれば、そのまま --ansi-scheme に指定できます。応答のなかった色は出力しません。
+			json: bool,
+			#[clap(long,default_value_t=1000)]
+			/// 応答を待つ時間をミリ秒で指定します。
+			timeout: u64
+		},
+		/// 描画性能を測定します。
+		Bench {
+			#[clap(subcommand)]
+			kind: BenchKind
 		},
 		/// visualizer コマンドの使い方を表示します。
 		Help
 	}
+	#[allow(clippy::upper_case_acronyms)]
 	pub type EDM = DrawMode;
 
+	#[derive(Subcommand,Clone)]
+	pub enum BenchKind {
+		/// 画像出力と同じ描画をスレッド数を変えて行い、並列化のスケーリングを測定します。画像の大きさとアンチエイリアスは --width, --height, --aa に従います。
+		Tiles {
+			#[clap(arg_enum,default_value_t=ModeName::Mandelbrot)]
+			/// 測定する描画モードを指定します。
+			mode: ModeName,
+			#[clap(short,long,default_value_t=3)]
+			/// 各スレッド数での試行回数を指定します。最も短い時間を採用します。
+			repeat: usize
+		},
+		/// 固定の負荷 (マンデルブロ集合とニュートン近似) をスレッド数を変えて描画し、所要時間、速度向上率、並列化効率とスコアを表示します。続けて最大スレッド数で scalar と simd の計算方法、 f64 と f32 の精度、全てのピクセルとアダプティブのアンチエイリアスの所要時間を比較します。ターミナルへの出力は測定に含まれません。最大スレッド数は --threads に従います。
+		Cpu {
+			#[clap(short,long,default_value_t=3)]
+			/// 各スレッド数での試行回数を指定します。最も短い時間を採用します。
+			repeat: usize
+		}
+	}
+
 }
 pub use external::*;
\ No newline at end of file
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::sync::Mutex;
+
+use crate::data::*;
+
+/// ジッターとポアソンディスクの乱数の種
+const SEED:u64 = 0x5eed_1234_abcd_0042;
+
+/// 生成したポアソンディスクのサンプル位置 (サンプル数ごと)
+/// 生成に時間がかかるため、ターミナルモードでフレームごとに生成し直さないように保持する。
+static POISSON_CACHE:Mutex<BTreeMap<usize,Vec<CF>>> = Mutex::new(BTreeMap::new());
+
+/// ガウシアンフィルタの標準偏差 (ピクセル単位)
+const GAUSSIAN_SIGMA:f64 = 0.5;
+
+/// アダプティブアンチエイリアスの既定の閾値 (隣のピクセルとの色の成分の差)
+pub const DEFAULT_AA_THRESHOLD:f64 = 0.02;
+
+/// アンチエイリアスのサンプル
+/// offsets はピクセルの左上からの正規化された座標でのずれ、 weights は offsets と同じ順の重み。
+/// weights が None なら全てのサンプルを等しく平均する。 height はピクセルの縦の大きさで、 stretched なら 2 になる。
+pub struct Samples {
+	pub offsets: Vec<CF>,
+	pub weights: Option<Vec<f64>>,
+	pub height:  u16
+}
+
+impl AaPattern {
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			AP::Grid    => "grid",
+			AP::Rotated => "rotated",
+			AP::Jitter  => "jitter",
+			AP::Halton  => "halton",
+			AP::Sobol   => "sobol",
+			AP::Poisson => "poisson"
+		}
+	}
+}
+
+impl AaFilter {
+
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			AF::Box      => "box",
+			AF::Tent     => "tent",
+			AF::Gaussian => "gaussian",
+			AF::Lanczos  => "lanczos"
+		}
+	}
+
+	/// フィルタの半径 (ピクセル単位)。ピクセルの中心からこの範囲のサンプルを重み付けして使う。
+	pub fn radius(&self) -> f64 {
+		match self {
+			AF::Box      => 0.5,
+			AF::Tent     => 1.0,
+			AF::Gaussian => 3.0*GAUSSIAN_SIGMA,
+			AF::Lanczos  => 2.0
+		}
+	}
+
+	/// ピクセルの中心からのずれ (ピクセル単位) に対する重み。縦横それぞれの1次元のフィルタの積とする。
+	pub fn weight(&self,x:f64,y:f64) -> f64 {
+		let f = |t:f64| match self {
+			AF::Box      => 1.0,
+			AF::Tent     => (1.0-t.abs()).max(0.0),
+			AF::Gaussian => (-t*t/(2.0*GAUSSIAN_SIGMA*GAUSSIAN_SIGMA)).exp(),
+			AF::Lanczos  => sinc(t)*sinc(t/2.0)
+		};
+		f(x)*f(y)
+	}
+
+}
+
+fn sinc(t:f64) -> f64 {
+	if t==0.0 { 1.0 } else { (PI*t).sin()/(PI*t) }
+}
+
+/// 単位正方形の中の nx × ny 個のサンプル位置
+pub fn pattern_points(pattern:AP,nx:usize,ny:usize) -> Vec<CF> {
+
+	let n = nx*ny;
+	let (fx,fy) = (nx as f64,ny as f64);
+
+	match pattern {
+		AP::Grid => (0..n)
+			.map(|k| (((k%nx) as f64+0.5)/fx,((k/nx) as f64+0.5)/fy))
+			.collect(),
+		// 各行・各列にサンプルが1つずつ入るように格子を傾けたもの (nx=ny=2 で RGSS と同じ配置)
+		AP::Rotated => (0..n)
+			.map(|k| {
+				let (i,j) = (k/ny,k%ny);
+				let x = ((i*ny+j) as f64+0.5)/(n as f64);
+				let y = ((j*nx+(nx-1-i)) as f64+0.5)/(n as f64);
+				(x,y)
+			})
+			.collect(),
+		AP::Jitter => {
+			let mut rng = Rng(SEED);
+			(0..n)
+				.map(|k| (((k%nx) as f64+rng.next())/fx,((k/nx) as f64+rng.next())/fy))
+				.collect()
+		},
+		AP::Halton => (1..=n)
+			.map(|k| (radical_inverse(k,2),radical_inverse(k,3)))
+			.collect(),
+		AP::Sobol => (0..n)
+			.map(|k| sobol(k as u32))
+			.collect(),
+		AP::Poisson => POISSON_CACHE.lock().unwrap().entry(n).or_insert_with(|| poisson(n)).clone()
+	}
+
+}
+
+/// 基数 base の radical inverse (ファン・デル・コルプト列)
+fn radical_inverse(mut k:usize,base:usize) -> f64 {
+	let mut inv = 1.0/(base as f64);
+	let mut v = 0.0;
+	while k>0 {
+		v += ((k%base) as f64)*inv;
+		k /= base;
+		inv /= base as f64;
+	}
+	v
+}
+
+/// ソボル列の最初の2次元
+/// 1次元目は2進数の radical inverse、2次元目は原始多項式 x+1 による方向数を使う。
+fn sobol(k:u32) -> CF {
+	let (mut x,mut y) = (0u32,0u32);
+	let mut v = 1u32<<31;
+	for b in 0..32 {
+		if k>>b & 1 == 1 {
+			x ^= 1<<(31-b);
+			y ^= v;
+		}
+		v ^= v>>1;
+	}
+	((x as f64)/4294967296.0,(y as f64)/4294967296.0)
+}
+
+/// ダーツ投げによるポアソンディスクサンプリング
+/// 最小距離を満たす点が見つからなくなったら最小距離を縮めて続ける。境界は周期的に扱う。
+fn poisson(n:usize) -> Vec<CF> {
+
+	let mut rng = Rng(SEED);
+	let mut points:Vec<CF> = vec![];
+	let mut r = 0.75/(n as f64).sqrt();
+
+	while points.len()<n {
+		let mut placed = false;
+		for _ in 0..1000 {
+			let p = (rng.next(),rng.next());
+			let far = points.iter().all(|q| {
+				let dx = (p.0-q.0).abs().min(1.0-(p.0-q.0).abs());
+				let dy = (p.1-q.1).abs().min(1.0-(p.1-q.1).abs());
+				dx.hypot(dy)>=r
+			});
+			if far {
+				points.push(p);
+				placed = true;
+				break;
+			}
+		}
+		if !placed { r *= 0.9; }
+	}
+
+	points
+
+}
+
+/// 固定の種による擬似乱数 (splitmix64)
+struct Rng(u64);
+
+impl Rng {
+	/// 0 以上 1 未満の一様乱数
+	fn next(&mut self) -> f64 {
+		self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+		let mut z = self.0;
+		z = (z^(z>>30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z^(z>>27)).wrapping_mul(0x94d049bb133111eb);
+		z ^= z>>31;
+		((z>>11) as f64)/((1u64<<53) as f64)
+	}
+}
@@ -1,78 +1,258 @@
-use rayon::prelude::*;
-
 use {
-	crate::data::*,
+	std::sync::atomic::Ordering,
+	crate::draw_lib::*,
 	crate::draw_func_color::color_fragment,
 	crate::draw_func_colorbar::colorbar_fragment,
-	crate::draw_func_mandelbrot::mandelbrot_fragment,
-	crate::draw_func_newton::newton_fragment,
-	crate::draw_func_ansi::*
+	crate::draw_func_cie::{cie_fragment,cie_position},
+	crate::draw_func_mandelbrot::{mandelbrot_fragment,mandelbrot_fragments,mandelbrot_position},
+	crate::draw_func_newton::{newton_fragment,newton_fragments},
+	crate::draw_func_ansi::*,
+	crate::colorspace::{srgb_decode,srgb_encode},
+	crate::draw_aa::*,
+	crate::scheme::Scheme
 };
 
 pub fn fragment(coord:CF,size:&CU,status:&Status) -> C {
 
 	match &status.draw_mode {
 		DM::Color(cs) => {
-			color_fragment(&coord,&size,&cs)
+			color_fragment(&coord,size,cs)
 		},
 		DM::Colorbar(cbs) => {
-			colorbar_fragment(&coord,&size,&cbs)
+			colorbar_fragment(&coord,size,cbs,status.levels)
+		},
+		DM::Cie(cs) => {
+			cie_fragment(&coord,size,cs)
 		},
-		DM::Mandelbrot => {
-			mandelbrot_fragment(&coord,&size)
+		DM::Mandelbrot(ms) => {
+			mandelbrot_fragment(&coord,size,ms,status.precision,status.palette.as_ref())
 		},
 		DM::NewtonApprox(nas) => {
-			newton_fragment(&coord,&size, &nas)
+			newton_fragment(&coord,size,nas,status.precision,status.palette.as_ref())
 		},
 		DM::Ansi => {
-			ansi_fragment(&coord,&size)
+			ansi_fragment(&coord,size)
 		}
 	}
 
 }
 
+/// 正規化された座標を各描画モードで使われる平面上の座標に変換する。
+pub fn fragment_position(coord:&CF,size:&CU,status:&Status) -> CF {
+	let center = || rationalize_coord(coord,size,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
+	match &status.draw_mode {
+		DM::Color(cs) if matches!(cs.mode,CSMode::Conic|CSMode::ConicW) => center(),
+		DM::Mandelbrot(ms) => mandelbrot_position(coord,size,ms),
+		DM::NewtonApprox(_) => center(),
+		DM::Cie(cs) => cie_position(coord,size,cs),
+		_ => *coord
+	}
+}
+
+/// ピクセルのサブピクセルの正規化された座標を返す。
+/// フィルタの範囲がピクセルの外に広がるサンプルも、画像の外の座標にならないよう画像の端に切り詰める。
 pub fn unify_coord(x:u16,y:u16,size:&CU,subpixels:&[CF]) -> Vec<CF> {
 
 	let x0 = (x as f64) / (size.0 as f64);
 	let y0 = (y as f64) / (size.1 as f64);
+	let edge = |v:f64| v.clamp(0.0,1.0-f64::EPSILON);
 
-	return subpixels.iter().map( |(x,y)| (x0+x,y0+y) ).collect();
+	subpixels.iter().map( |(x,y)| (edge(x0+x),edge(y0+y)) ).collect()
 
 }
 
-pub fn get_color(x:u16,y:u16,size:&CU,s:&Status,subpixels:&[CF]) -> C {
-	let coords = unify_coord(x,y,size,subpixels);
-	match s.aa {
-		0 => fragment(coords[0],size,s),
-		_ => {
-			let ct = coords
-				.into_iter().par_bridge()
-				.map( |coord| {
-					let c = fragment(coord,size,s);
-					match c {
-						C::Float {r,g,b,a} => {
-							let aas = subpixels.len() as f64;
-							( r/aas , g/aas , b/aas , a/aas )
-						},
-						C::GFloat {v,a} => {
-							let aas = subpixels.len() as f64;
-							( v/aas , v/aas , v/aas , a/aas )
-						},
-						_ => { panic!(); }
-					}
-				} )
-				.reduce(
-					|| (0.0,0.0,0.0,0.0),
-					|c1,c2| {
-						( c1.0+c2.0 , c1.1+c2.1 , c1.2+c2.2 , c1.3+c2.3 )
-					}
-				);
-			C::Float { r:ct.0, g:ct.1, b:ct.2, a:ct.3 }
-		}
+/// 複数の座標のフラグメントをまとめて計算する。
+/// --kernel simd の場合、レーン単位で計算できる描画モードは複数の点を同時に計算する。
+pub fn fragments(coords:&[CF],size:&CU,status:&Status) -> Vec<C> {
+	match (&status.draw_mode,status.kernel) {
+		(DM::Mandelbrot(ms),KN::Simd) => mandelbrot_fragments(coords,size,ms,status.precision,status.palette.as_ref()),
+		(DM::NewtonApprox(nas),KN::Simd) => newton_fragments(coords,size,nas,status.precision,status.palette.as_ref()),
+		_ => coords.iter().map(|coord| fragment(*coord,size,status)).collect()
+	}
+}
+
+/// 複数のピクセルの色をまとめて計算する。
+/// アダプティブアンチエイリアスが有効なら、境界のピクセルだけをスーパーサンプリングする。
+pub fn get_colors(pixels:&[CU],size:&CU,s:&Status,samples:&Samples) -> Vec<C> {
+	match s.aa_threshold {
+		Some(threshold) if samples.offsets.len()>1 => adaptive_colors(pixels,size,s,samples,threshold),
+		_ => full_colors(pixels,size,s,samples)
+	}
+}
+
+/// 全てのピクセルのサブピクセルを一度に fragments に渡して色を計算する。
+fn full_colors(pixels:&[CU],size:&CU,s:&Status,samples:&Samples) -> Vec<C> {
+
+	let subpixels = samples.offsets.as_slice();
+	let coords:Vec<CF> = pixels.iter()
+		.flat_map(|&(x,y)| unify_coord(x,y,size,subpixels))
+		.collect();
+	s.samples.fetch_add(coords.len(),Ordering::Relaxed);
+	let mut cs = fragments(&coords,size,s);
+
+	match subpixels.len() {
+		1 if samples.weights.is_none() => cs,
+		n => cs.chunks_mut(n).map(|chunk| resolve(chunk,&samples.weights,s)).collect()
+	}
+}
+
+/// 1つのピクセルのサブピクセルの色をまとめる。
+/// 全てのサブピクセルが同じ端末の色 (C::Int, C::Ansi, 色名, C::Reverse) なら、平均せずにその色をそのまま使う。
+fn resolve(chunk:&mut [C],weights:&Option<Vec<f64>>,s:&Status) -> C {
+	if chunk[0].is_terminal() && chunk.iter().all(|c| *c==chunk[0]) {
+		return std::mem::replace(&mut chunk[0],C::None);
+	}
+	match weights {
+		None    => average(chunk.iter(),chunk.len(),s.linear_aa,&s.scheme),
+		Some(w) => weighted_average(chunk.iter(),w,s.linear_aa,&s.scheme)
+	}
+}
+
+/// 各ピクセルの中心を1回ずつ計算し、上下左右のピクセルの中心との色の差が threshold を超えるピクセルだけを
+/// full_colors でスーパーサンプリングする。それ以外のピクセルは中心の色をそのまま使う。
+/// 隣のピクセルがタイルの外にあっても判定できるように、ピクセルを囲む1ピクセル外側まで中心を計算する。
+/// 画像の外にある隣のピクセルは計算せず、中心と同じ色として扱う。
+fn adaptive_colors(pixels:&[CU],size:&CU,s:&Status,samples:&Samples,threshold:f64) -> Vec<C> {
+
+	if pixels.is_empty() { return vec![]; }
+
+	// ピクセルの並びを縦方向に height ごとの格子として扱う
+	let step = samples.height as i32;
+	let (min_x,max_x) = (pixels.iter().map(|p| p.0 as i32).min().unwrap(),pixels.iter().map(|p| p.0 as i32).max().unwrap());
+	let (min_y,max_y) = (pixels.iter().map(|p| p.1 as i32).min().unwrap(),pixels.iter().map(|p| p.1 as i32).max().unwrap());
+	let x0 = if min_x>0 { min_x-1 } else { min_x };
+	let y0 = if min_y>=step { min_y-step } else { min_y };
+	let x1 = if max_x+1<size.0 as i32 { max_x+1 } else { max_x };
+	let y1 = if max_y+step<size.1 as i32 { max_y+step } else { max_y };
+	let (w,h) = ((x1-x0+1) as usize,((y1-y0)/step+1) as usize);
+	let index = |x:i32,y:i32| (((y-y0)/step) as usize)*w + ((x-x0) as usize);
+	let inside = |x:i32,y:i32| x>=x0 && x<=x1 && y>=y0 && y<=y1;
+
+	let (sx,sy) = (size.0 as f64,size.1 as f64);
+	let coords:Vec<CF> = (0..w*h)
+		.map(|i| {
+			let (x,y) = ((i%w) as i32+x0,((i/w) as i32)*step+y0);
+			(((x as f64)+0.5)/sx,((y as f64)+0.5*(step as f64))/sy)
+		})
+		.collect();
+	s.samples.fetch_add(coords.len(),Ordering::Relaxed);
+	let mut centers = fragments(&coords,size,s);
+
+	let refine:Vec<bool> = pixels.iter()
+		.map(|&(x,y)| {
+			let (x,y) = (x as i32,y as i32);
+			let c = &centers[index(x,y)];
+			[(x-1,y),(x+1,y),(x,y-step),(x,y+step)].iter()
+				.filter(|&&(nx,ny)| inside(nx,ny))
+				.any(|&(nx,ny)| color_difference(c,&centers[index(nx,ny)],&s.scheme)>threshold)
+		})
+		.collect();
+
+	let edges:Vec<CU> = pixels.iter().zip(&refine).filter(|(_,r)| **r).map(|(p,_)| *p).collect();
+	let mut refined = full_colors(&edges,size,s,samples).into_iter();
+
+	pixels.iter().zip(refine)
+		.map(|(&(x,y),r)| match r {
+			true  => refined.next().unwrap(),
+			false => std::mem::replace(&mut centers[index(x as i32,y as i32)],C::None)
+		})
+		.collect()
+
+}
+
+/// 2つの色の乗算済みアルファの RGBA の成分の差の最大値
+fn color_difference(c1:&C,c2:&C,scheme:&Scheme) -> f64 {
+	let (a,b) = (c1.rgba(scheme),c2.rgba(scheme));
+	(a.0*a.3-b.0*b.3).abs()
+		.max((a.1*a.3-b.1*b.3).abs())
+		.max((a.2*a.3-b.2*b.3).abs())
+		.max((a.3-b.3).abs())
+}
+
+/// サブピクセルの色を平均する。
+/// アルファは乗算済みとして平均するため、 C::None は透明として混ざる。
+/// linear が true なら sRGB の符号化を戻した線形な光の量で平均し、平均した後に符号化し直す。
+/// 全てのサブピクセルが不透明なら linear が false の場合の結果は以前の平均と一致する。
+fn average<'a,I:Iterator<Item=&'a C>>(cs:I,n:usize,linear:bool,scheme:&Scheme) -> C {
+
+	let aas = n as f64;
+	let decode = |v:f64| if linear { srgb_decode(v) } else { v };
+	let encode = |v:f64| if linear { srgb_encode(v) } else { v };
+
+	let mut opaque = true;
+	let ct = cs
+		.map( |c| {
+			let (r,g,b,a) = c.rgba(scheme);
+			if a!=1.0 { opaque = false; }
+			( decode(r)*a/aas , decode(g)*a/aas , decode(b)*a/aas , a/aas )
+		} )
+		.fold(
+			(0.0,0.0,0.0,0.0),
+			|c1,c2| {
+				( c1.0+c2.0 , c1.1+c2.1 , c1.2+c2.2 , c1.3+c2.3 )
+			}
+		);
+
+	if ct.3<=0.0 { return C::None; }
+	let straight = |v:f64| if opaque { v } else { v/ct.3 };
+	C::Float { r:encode(straight(ct.0)), g:encode(straight(ct.1)), b:encode(straight(ct.2)), a:ct.3 }
+
+}
+
+/// アンチエイリアスのサンプルを生成する。
+/// grid と box の組み合わせは aa × aa の格子をピクセルの左上から並べ、等しく平均する。
+/// それ以外はピクセルの中心からフィルタの半径までの範囲にパターンのサンプルを並べ、フィルタの重みを付ける。
+/// 範囲が広いフィルタほど、サンプルの密度が変わらないようにサンプル数を増やす。
+/// stretched の場合、1つのピクセルは縦に2つ分の高さを持つ。
+pub fn aa_subpixels(aa:u8,size:&CU,stretched:bool,pattern:AP,filter:AF) -> Samples {
+
+	if aa<2 || (pattern==AP::Grid && filter==AF::Box) {
+		return Samples { offsets:grid_subpixels(aa,size,stretched), weights:None, height:if stretched { 2 } else { 1 } };
 	}
+
+	let (px,py) = (1.0/(size.0 as f64),1.0/(size.1 as f64));
+	let height = if stretched { 2.0 } else { 1.0 };
+	let r = filter.radius();
+	let nx = ((aa as f64)*2.0*r).ceil() as usize;
+	let ny = ((aa as f64)*2.0*r*height).ceil() as usize;
+
+	let (offsets,weights) = pattern_points(pattern,nx,ny).into_iter()
+		.map(|(u,v)| {
+			// ピクセルの中心からのずれ (ピクセル単位、縦は stretched の高さを1とする)
+			let (x,y) = ((u-0.5)*2.0*r,(v-0.5)*2.0*r);
+			let offset = ((0.5+x)*px,(0.5+y)*height*py);
+			(offset,filter.weight(x,y))
+		})
+		.unzip();
+
+	Samples { offsets, weights:Some(weights), height:height as u16 }
+
+}
+
+/// サブピクセルの色をフィルタの重みで平均する。
+/// 重みの和で割るため、負の重みを持つフィルタでも明るさは変わらない。結果は 0 から 1 に切り詰める。
+fn weighted_average<'a,I:Iterator<Item=&'a C>>(cs:I,weights:&[f64],linear:bool,scheme:&Scheme) -> C {
+
+	let decode = |v:f64| if linear { srgb_decode(v) } else { v };
+	let encode = |v:f64| if linear { srgb_encode(v) } else { v };
+	let total:f64 = weights.iter().sum();
+
+	let ct = cs.zip(weights)
+		.map( |(c,w)| {
+			let (r,g,b,a) = c.rgba(scheme);
+			let k = a*w/total;
+			( decode(r)*k , decode(g)*k , decode(b)*k , k )
+		} )
+		.fold((0.0,0.0,0.0,0.0),|c1,c2| ( c1.0+c2.0 , c1.1+c2.1 , c1.2+c2.2 , c1.3+c2.3 ));
+
+	let a = ct.3.clamp(0.0,1.0);
+	if a<=0.0 { return C::None; }
+	let straight = |v:f64| encode((v/ct.3).clamp(0.0,1.0));
+	C::Float { r:straight(ct.0), g:straight(ct.1), b:straight(ct.2), a }
+
 }
 
-pub fn aa_subpixels(aa:u8,size:&CU,stretched:bool) -> Vec<CF> {
+fn grid_subpixels(aa:u8,size:&CU,stretched:bool) -> Vec<CF> {
 
 	let     dx = 1.0 / (size.0 as f64) / (aa.max(1) as f64);
 	let mut dy = 1.0 / (size.1 as f64) / (aa.max(1) as f64);
@@ -86,9 +266,9 @@ pub fn aa_subpixels(aa:u8,size:&CU,stretched:bool) -> Vec<CF> {
 		(false,_) => aa.max(1) as u16
 	};
 
-	return iproduct!(0..ax,0..ay)
+	iproduct!(0..ax,0..ay)
 		.map(|(i,j)| {
 			( (i as f64)*dx , (j as f64)*dy )
-		}).collect();
+		}).collect()
 
 }
\ No newline at end of file
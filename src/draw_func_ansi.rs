@@ -1,7 +1,10 @@
 use crate::draw_lib::*;
 
+/// ANSIテストの色を返す。画像の外の座標は C::None とする。
 pub fn ansi_fragment(c:&CF,s:&CU) -> C {
 
+	if !(0.0..1.0).contains(&c.0) || !(0.0..1.0).contains(&c.1) { return C::None; }
+
 	let wide = s.0>=(s.1*2);
 	let (gn,gc) = match wide {
 		false => (
@@ -33,12 +36,11 @@ pub fn ansi_fragment(c:&CF,s:&CU) -> C {
 				match (cc*25.0).round() as u8 {
 					0 => 0x10,
 					v if v>0 && v<25 => v+0xe7,
-					25 => 0xe7,
-					_ => { panic!(); }
+					_ => 0xe7
 				}
 			),
 			(1,1) => C::GFloat{v:cc,a:1.0},
-			_ => { panic!(); }
+			_ => C::None
 		}
 	}
 	else {
@@ -62,7 +64,7 @@ pub fn ansi_fragment(c:&CF,s:&CU) -> C {
 					(6,1) => C::CD,
 					(7,0) => C::WL,
 					(7,1) => C::WD,
-					_ => { panic!(); }
+					_ => C::None
 				}
 			},
 			2 => C::Ansi(cn.0+8*(1-cn.1)),
@@ -84,38 +86,32 @@ pub fn ansi_fragment(c:&CF,s:&CU) -> C {
 					(6,1) => C::Int{r:0x00,g:0x80,b:0x80,a:0xff},
 					(7,0) => C::Int{r:0xff,g:0xff,b:0xff,a:0xff},
 					(7,1) => C::Int{r:0xc0,g:0xc0,b:0xc0,a:0xff},
-					_ => { panic!(); }
+					_ => C::None
 				}
 			},
 			4 => {
-				C::Ansi(
-					match cn {
-						(0,0) => 244,
-						(0,1) =>  16,
-						(7,0) => 231,
-						(7,1) => 250,
-						_     => {
-							let (r,g,b) = match cn {
-								(1,0) => (5,0,0),
-								(1,1) => (2,0,0),
-								(2,0) => (0,5,0),
-								(2,1) => (0,2,0),
-								(3,0) => (5,5,0),
-								(3,1) => (2,2,0),
-								(4,0) => (0,0,5),
-								(4,1) => (0,0,2),
-								(5,0) => (5,0,5),
-								(5,1) => (2,0,2),
-								(6,0) => (0,5,5),
-								(6,1) => (0,2,2),
-								_ => { panic!(); }
-							};
-							r*36+g*6+b+0x10
-						}
-					}
-				)
+				let cube = |r:u8,g:u8,b:u8| C::Ansi(r*36+g*6+b+0x10);
+				match cn {
+					(0,0) => C::Ansi(244),
+					(0,1) => C::Ansi( 16),
+					(7,0) => C::Ansi(231),
+					(7,1) => C::Ansi(250),
+					(1,0) => cube(5,0,0),
+					(1,1) => cube(2,0,0),
+					(2,0) => cube(0,5,0),
+					(2,1) => cube(0,2,0),
+					(3,0) => cube(5,5,0),
+					(3,1) => cube(2,2,0),
+					(4,0) => cube(0,0,5),
+					(4,1) => cube(0,0,2),
+					(5,0) => cube(5,0,5),
+					(5,1) => cube(2,0,2),
+					(6,0) => cube(0,5,5),
+					(6,1) => cube(0,2,2),
+					_ => C::None
+				}
 			},
-			_ => { panic!(); }
+			_ => C::None
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,226 @@
+use crate::draw_lib::*;
+use crate::colorspace::{xyz_to_linear,srgb_encode};
+
+/// CIE 1931 2° 等色関数によるスペクトル軌跡の xy 色度座標 (380nm から 700nm まで 5nm 間隔)
+/// 700nm より長い波長はほぼ同じ点に重なるため省略する。
+const LOCUS:&[CF] = &[
+	(0.1741,0.0050),(0.1740,0.0050),(0.1738,0.0049),(0.1736,0.0049),(0.1733,0.0048),
+	(0.1730,0.0048),(0.1726,0.0048),(0.1721,0.0048),(0.1714,0.0051),(0.1703,0.0058),
+	(0.1689,0.0069),(0.1669,0.0086),(0.1644,0.0109),(0.1611,0.0138),(0.1566,0.0177),
+	(0.1510,0.0227),(0.1440,0.0297),(0.1355,0.0399),(0.1241,0.0578),(0.1096,0.0868),
+	(0.0913,0.1327),(0.0687,0.2007),(0.0454,0.2950),(0.0235,0.4127),(0.0082,0.5384),
+	(0.0039,0.6548),(0.0139,0.7502),(0.0389,0.8120),(0.0743,0.8338),(0.1142,0.8262),
+	(0.1547,0.8059),(0.1929,0.7816),(0.2296,0.7543),(0.2658,0.7243),(0.3016,0.6923),
+	(0.3373,0.6589),(0.3731,0.6245),(0.4087,0.5896),(0.4441,0.5547),(0.4788,0.5202),
+	(0.5125,0.4866),(0.5448,0.4544),(0.5752,0.4242),(0.6029,0.3965),(0.6270,0.3725),
+	(0.6482,0.3514),(0.6658,0.3340),(0.6801,0.3197),(0.6915,0.3083),(0.7006,0.2993),
+	(0.7079,0.2920),(0.7140,0.2859),(0.7190,0.2809),(0.7230,0.2770),(0.7260,0.2740),
+	(0.7283,0.2717),(0.7300,0.2700),(0.7311,0.2689),(0.7320,0.2680),(0.7327,0.2673),
+	(0.7334,0.2666),(0.7340,0.2660),(0.7344,0.2656),(0.7346,0.2654),(0.7347,0.2653)
+];
+
+/// 色域の三角形 (赤,緑,青の原色の xy 色度座標) と線の模様 (実線の長さ,間隔の長さ,画素単位)
+/// 模様は sRGB が実線、 Display P3 が破線、 Rec.2020 が点線、 Adobe RGB が一点鎖線。
+const GAMUTS:&[([CF;3],&[f64])] = &[
+	([(0.640,0.330),(0.300,0.600),(0.150,0.060)],&[]),
+	([(0.680,0.320),(0.265,0.690),(0.150,0.060)],&[8.0,4.0]),
+	([(0.708,0.292),(0.170,0.797),(0.131,0.046)],&[2.0,3.0]),
+	([(0.640,0.330),(0.210,0.710),(0.150,0.060)],&[10.0,3.0,2.0,3.0])
+];
+
+/// 格子の間隔と背景の明るさ
+const GRID:f64 = 0.1;
+const BACKGROUND:f64 = 0.1;
+
+/// 線の太さの半分と白色点の印の半径 (画素単位)
+const LINE:f64 = 0.8;
+const MARK:f64 = 4.0;
+
+/// 図の範囲 (xy では 0-0.8 × 0-0.9、 u'v' では 0-0.7 × 0-0.7) と縦横比
+fn chart_range(uv:bool) -> (CF,CU) {
+	match uv {
+		false => ((0.8,0.9),(8,9)),
+		true  => ((0.7,0.7),(1,1))
+	}
+}
+
+impl WhitePoint {
+
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			WP::D65 => "d65",
+			WP::D50 => "d50",
+			WP::D55 => "d55",
+			WP::D75 => "d75",
+			WP::A   => "a",
+			WP::C   => "c",
+			WP::E   => "e",
+			WP::Dci => "dci"
+		}
+	}
+
+	/// xy 色度座標
+	pub fn xy(&self) -> CF {
+		match self {
+			WP::D65 => (0.3127,0.3290),
+			WP::D50 => (0.3457,0.3585),
+			WP::D55 => (0.3324,0.3474),
+			WP::D75 => (0.2990,0.3149),
+			WP::A   => (0.4476,0.4074),
+			WP::C   => (0.3101,0.3162),
+			WP::E   => (1.0/3.0,1.0/3.0),
+			WP::Dci => (0.3140,0.3510)
+		}
+	}
+
+	pub fn next(&self) -> WP {
+		match self {
+			WP::D65 => WP::D50,
+			WP::D50 => WP::D55,
+			WP::D55 => WP::D75,
+			WP::D75 => WP::A,
+			WP::A   => WP::C,
+			WP::C   => WP::E,
+			WP::E   => WP::Dci,
+			WP::Dci => WP::D65
+		}
+	}
+
+}
+
+pub fn cie_fragment(c:&CF,s:&CU,cs:&CieStatus) -> C {
+
+	let (range,ratio) = chart_range(cs.uv);
+	// 図の外側も背景として塗り、アンチエイリアスで平均できるようにする
+	let p = match rationalize_coord(c,s,ratio,RCOverflow::Discard,RCOrigin::TopLeft) {
+		Some(p) => (p.0*range.0,(1.0-p.1)*range.1),
+		None => { return C::GFloat{v:BACKGROUND,a:1.0}; }
+	};
+
+	// 図の1画素の大きさ
+	let px = range.0/(s.0 as f64).min((s.1 as f64)*(ratio.0 as f64)/(ratio.1 as f64));
+	let chart = |q:CF| if cs.uv { xy_to_uv(q) } else { q };
+
+	let white = chart(cs.white.xy());
+	let d = (p.0-white.0).hypot(p.1-white.1);
+	if (d-MARK*px).abs()<=LINE*px || d<=LINE*px { return C::GFloat{v:0.0,a:1.0}; }
+
+	for (primaries,dash) in GAMUTS.iter() {
+		let v = primaries.map(chart);
+		for i in 0..3 {
+			if on_line(p,v[i],v[(i+1)%3],px,dash) { return C::GFloat{v:0.0,a:1.0}; }
+		}
+	}
+
+	let xy = if cs.uv { uv_to_xy(p) } else { p };
+	let locus:Vec<CF> = LOCUS.iter().map(|q| chart(*q)).collect();
+	if inside(p,&locus) {
+		let (r,g,b) = chromaticity_color(xy);
+		return C::Float{r,g,b,a:1.0};
+	}
+
+	let on_grid = |v:f64| (v/GRID-(v/GRID).round()).abs()*GRID<=LINE*px/2.0;
+	match on_grid(p.0) || on_grid(p.1) {
+		true  => C::GFloat{v:0.3,a:1.0},
+		false => C::GFloat{v:BACKGROUND,a:1.0}
+	}
+
+}
+
+/// 図の上の位置 (xy または u'v' の色度座標)
+pub fn cie_position(c:&CF,s:&CU,cs:&CieStatus) -> CF {
+	let (range,ratio) = chart_range(cs.uv);
+	let p = rationalize_coord(c,s,ratio,RCOverflow::Keep,RCOrigin::TopLeft).unwrap();
+	(p.0*range.0,(1.0-p.1)*range.1)
+}
+
+/// 色度座標 xy の色を、表示できる最も近い色として sRGB で返す。
+/// sRGB の範囲外の色は負の成分がなくなるまで白を加え、最大の成分が 1 になるように明るさを揃える。
+fn chromaticity_color(xy:CF) -> (f64,f64,f64) {
+	let (x,y) = xy;
+	let (r,g,b) = xyz_to_linear((x/y,1.0,(1.0-x-y)/y));
+	let m = r.min(g).min(b).min(0.0);
+	let (r,g,b) = (r-m,g-m,b-m);
+	let n = r.max(g).max(b);
+	(srgb_encode(r/n),srgb_encode(g/n),srgb_encode(b/n))
+}
+
+/// 点 p が線分 a-b の線の上にあるかどうか。 dash が空でなければ画素単位の長さで実線と間隔を繰り返す。
+fn on_line(p:CF,a:CF,b:CF,px:f64,dash:&[f64]) -> bool {
+
+	let (dx,dy) = (b.0-a.0,b.1-a.1);
+	let len2 = dx*dx+dy*dy;
+	let t = (((p.0-a.0)*dx+(p.1-a.1)*dy)/len2).clamp(0.0,1.0);
+	let q = (a.0+t*dx,a.1+t*dy);
+	if (p.0-q.0).hypot(p.1-q.1)>LINE*px { return false; }
+	if dash.is_empty() { return true; }
+
+	let period:f64 = dash.iter().sum();
+	let mut pos = (t*len2.sqrt()/px).rem_euclid(period);
+	for (i,l) in dash.iter().enumerate() {
+		if pos<*l { return i%2==0; }
+		pos -= l;
+	}
+	false
+
+}
+
+/// 点 p が多角形の内側にあるかどうか (偶奇規則)
+fn inside(p:CF,polygon:&[CF]) -> bool {
+	let mut result = false;
+	let mut j = polygon.len()-1;
+	for i in 0..polygon.len() {
+		let (a,b) = (polygon[i],polygon[j]);
+		if (a.1>p.1)!=(b.1>p.1) && p.0<(b.0-a.0)*(p.1-a.1)/(b.1-a.1)+a.0 {
+			result = !result;
+		}
+		j = i;
+	}
+	result
+}
+
+/// CIE 1931 xy から CIE 1976 u'v' へ
+fn xy_to_uv(xy:CF) -> CF {
+	let d = -2.0*xy.0+12.0*xy.1+3.0;
+	(4.0*xy.0/d,9.0*xy.1/d)
+}
+
+/// CIE 1976 u'v' から CIE 1931 xy へ
+fn uv_to_xy(uv:CF) -> CF {
+	let d = 6.0*uv.0-16.0*uv.1+12.0;
+	(9.0*uv.0/d,4.0*uv.1/d)
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	fn close(a:(f64,f64,f64),b:(f64,f64,f64)) -> bool {
+		(a.0-b.0).abs()<1e-3 && (a.1-b.1).abs()<1e-3 && (a.2-b.2).abs()<1e-3
+	}
+
+	#[test]
+	fn uv_of_white_points() {
+		let (u,v) = xy_to_uv(WP::D65.xy());
+		assert!((u-0.1978).abs()<1e-4 && (v-0.4683).abs()<1e-4);
+		let (u,v) = xy_to_uv(WP::E.xy());
+		assert!((u-4.0/19.0).abs()<1e-12 && (v-9.0/19.0).abs()<1e-12);
+		for wp in [WP::D50,WP::A,WP::Dci] {
+			let (x,y) = uv_to_xy(xy_to_uv(wp.xy()));
+			assert!((x-wp.xy().0).abs()<1e-12 && (y-wp.xy().1).abs()<1e-12);
+		}
+	}
+
+	#[test]
+	fn chromaticity_colors() {
+		// D65 の白色点と sRGB の原色は sRGB の白と原色になる
+		assert!(close(chromaticity_color(WP::D65.xy()),(1.0,1.0,1.0)));
+		assert!(close(chromaticity_color((0.64,0.33)),(1.0,0.0,0.0)));
+		assert!(close(chromaticity_color((0.30,0.60)),(0.0,1.0,0.0)));
+		assert!(close(chromaticity_color((0.15,0.06)),(0.0,0.0,1.0)));
+		assert!(inside(WP::D65.xy(),LOCUS));
+		assert!(!inside((0.7,0.7),LOCUS));
+	}
+
+}
@@ -1,9 +1,10 @@
 use crate::draw_lib::*;
+use crate::colorspace::lch_to_srgb;
 
 pub fn color_fragment(c:&CF,s:&CU,cs:&ColorStatus) -> C {
 	match cs.mode {
 		CSMode::Conic|CSMode::ConicW => hue_fragment(c,s,cs),
-		CSMode::BV|CSMode::BVW => bvw_fragment(c,cs)
+		CSMode::BV|CSMode::BVW => bvw_fragment(c,s,cs)
 	}
 }
 
@@ -23,11 +24,19 @@ pub fn color_mouse_drag(c:&CF,s:&CU,cs:&mut ColorStatus) {
 
 fn hue_fragment(c:&CF,s:&CU,cs:&ColorStatus) -> C {
 
-	let p = rationalize_coord(c,&s,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
+	let p = rationalize_coord(c,s,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
 	let     radius = p.1.hypot(p.0);
 	let mut theta  = p.1.atan2(p.0)*RAD2DEG;
 	theta = (theta-cs.angle).rem_euclid(360.0);
 
+	if cs.space!=CSP::Rgb {
+		let chroma = match cs.mode {
+			CSMode::ConicW => cs.chroma*radius.min(1.0),
+			_ => cs.chroma
+		};
+		return wheel_color(c,s,cs.space,cs.lightness,chroma,theta);
+	}
+
 	match radius {
 		d if d>0.0 => {
 			let (r,g,b) = deg2rgb(theta);
@@ -63,8 +72,15 @@ fn hue_mouse_drag(c:&CF,s:&CU,cs:&mut ColorStatus) {
 
 }
 
-fn bvw_fragment(c:&CF,cs:&ColorStatus) -> C {
+fn bvw_fragment(c:&CF,s:&CU,cs:&ColorStatus) -> C {
 	let theta = (c.0*360.0+cs.angle).rem_euclid(360.0);
+
+	match (cs.space,&cs.mode) {
+		(CSP::Rgb,_) => {},
+		(space,CSMode::BVW) => { return wheel_color(c,s,space,100.0*(1.0-c.1),cs.chroma,theta); },
+		(space,_) => { return wheel_color(c,s,space,cs.lightness*(1.0-c.1),cs.chroma*(1.0-c.1),theta); }
+	}
+
 	let hue = deg2rgb(theta);
 
 	match cs.mode {
@@ -99,6 +115,21 @@ fn bvw_fragment(c:&CF,cs:&ColorStatus) -> C {
 
 }
 
+/// 色空間の明度・彩度・色相による色。 sRGB の範囲外の色は切り詰めた色と灰色の斜めの縞模様にする。
+fn wheel_color(c:&CF,s:&CU,space:CSP,l:f64,chroma:f64,h:f64) -> C {
+	let ((r,g,b),in_gamut) = lch_to_srgb(space,l,chroma,h);
+	match in_gamut || !gamut_stripe(c,s) {
+		true  => C::Float{r,g,b,a:1.0},
+		false => C::GFloat{v:0.5,a:1.0}
+	}
+}
+
+/// 範囲外の色を示す縞模様で灰色にする側かどうか
+fn gamut_stripe(c:&CF,s:&CU) -> bool {
+	let w = ((s.0.min(s.1) as f64)/32.0).max(2.0);
+	((c.0*(s.0 as f64)+c.1*(s.1 as f64))/w).floor().rem_euclid(2.0)==0.0
+}
+
 fn bvw_mouse_down(c:&CF,cs:&mut ColorStatus) {
 	cs.mouse_position = (c.0,c.1);
 }
@@ -111,4 +142,24 @@ fn bvw_mouse_drag(c:&CF,cs:&mut ColorStatus) {
 	let angle_diff = (mpc.0-mpp.0)*360.0;
 	cs.angle = (cs.angle-angle_diff).rem_euclid(360.0);
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	#[test]
+	fn wheel_color_gamut_stripe() {
+		let s:CU = (64,64);
+		// 縞模様の幅は2ピクセルで、 (0,0) は灰色の側、 (2,0) は切り詰めた色の側
+		let (on,off) = ((0.0,0.0),(2.0/64.0,0.0));
+		assert!(wheel_color(&on,&s,CSP::Lch,50.0,100.0,0.0)==C::GFloat{v:0.5,a:1.0});
+		let ((r,g,b),_) = lch_to_srgb(CSP::Lch,50.0,100.0,0.0);
+		assert!(wheel_color(&off,&s,CSP::Lch,50.0,100.0,0.0)==C::Float{r,g,b,a:1.0});
+		match wheel_color(&on,&s,CSP::Hsluv,100.0,100.0,0.0) {
+			C::Float{r,g,b,..} => assert!((r-1.0).abs()<1e-9 && (g-1.0).abs()<1e-9 && (b-1.0).abs()<1e-9),
+			_ => panic!()
+		}
+	}
+
+}
@@ -1,30 +1,326 @@
 use crate::draw_lib::*;
+use crate::colorspace::{bt709_to_bt2020,pq_encode,pq_decode,hlg_encode,hlg_decode};
 
-pub fn colorbar_fragment(c:&CF,s:&CU,cbs:&ColorbarStatus) -> C {
+/// 公称の R'G'B' の信号レベル
+/// 0 が黒、 1 が白で、 PLUGE や I/Q のパッチでは範囲外の値も持つ。
+pub type Level = (f64,f64,f64);
 
-	let p_opt = rationalize_coord(
-		c,&s,
+/// 信号レベルを levels の範囲の画素値にする。画素値の 0 から 1 の範囲外の値は出力時に切り詰める。
+pub fn colorbar_fragment(c:&CF,s:&CU,cbs:&ColorbarStatus,levels:VL) -> C {
+	match colorbar_level(c,s,cbs,levels) {
+		Some((r,g,b)) => C::Float{r:levels.encode(r),g:levels.encode(g),b:levels.encode(b),a:1.0},
+		None => C::None
+	}
+}
+
+impl CBSMode {
+
+	/// Y'CbCr の輝度の係数 (Kr, Kb)
+	/// HD の図は BT.709、 SD の図は BT.601 とする。
+	pub fn luma(&self) -> (f64,f64) {
+		match self {
+			CBSMode::RP219(_)|CBSMode::ARIB => (0.2126,0.0722),
+			CBSMode::SMPTE|CBSMode::EBU{..} => (0.299 ,0.114 ),
+			CBSMode::HDR(_) => (0.2627,0.0593)
+		}
+	}
+
+	/// 同じ描画モードの中で次の種類のカラーバー
+	/// smpte は SMPTE, RP 219 の各パッチの順、 ebu は 75%, 100%, それぞれのスプリットフィールドの順、 hdr は pq, hlg の順とする。
+	pub fn next(&self) -> CBSMode {
+		match self {
+			CBSMode::SMPTE => CBSMode::RP219(HdPatch::PlusI),
+			CBSMode::RP219(HdPatch::White100) => CBSMode::SMPTE,
+			CBSMode::RP219(patch) => CBSMode::RP219(patch.next()),
+			CBSMode::ARIB => CBSMode::ARIB,
+			CBSMode::EBU{full,split} => CBSMode::EBU{full:!full,split:split^full},
+			CBSMode::HDR(tf) => CBSMode::HDR(tf.next())
+		}
+	}
+
+}
+
+impl VideoLevels {
+
+	/// 信号レベルを画素値にする。 limited では黒を 16/255、白を 235/255 とする。
+	pub fn encode(&self,v:f64) -> f64 {
+		match self {
+			VL::Full    => v,
+			VL::Limited => (16.0+219.0*v)/255.0
+		}
+	}
+
+	/// 画素値を信号レベルに戻す。
+	pub fn decode(&self,v:f64) -> f64 {
+		match self {
+			VL::Full    => v,
+			VL::Limited => (v*255.0-16.0)/219.0
+		}
+	}
+
+	pub fn next(&self) -> VL {
+		match self {
+			VL::Full    => VL::Limited,
+			VL::Limited => VL::Full
+		}
+	}
+
+}
+
+impl Transfer {
+
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			Transfer::Pq  => "pq",
+			Transfer::Hlg => "hlg"
+		}
+	}
+
+	pub fn next(&self) -> Transfer {
+		match self {
+			Transfer::Pq  => Transfer::Hlg,
+			Transfer::Hlg => Transfer::Pq
+		}
+	}
+
+	/// HDR の基準白 (203 cd/m²) の信号レベル
+	fn reference(&self) -> f64 {
+		match self {
+			Transfer::Pq  => 0.58,
+			Transfer::Hlg => 0.75
+		}
+	}
+
+	/// 信号レベルを線形な値にする。 PQ は輝度 (cd/m²)、 HLG はシーンの線形な値とする。
+	fn decode(&self,e:f64) -> f64 {
+		match self {
+			Transfer::Pq  => pq_decode(e),
+			Transfer::Hlg => hlg_decode(e)
+		}
+	}
+
+	fn encode(&self,l:f64) -> f64 {
+		match self {
+			Transfer::Pq  => pq_encode(l),
+			Transfer::Hlg => hlg_encode(l)
+		}
+	}
+
+	/// 信号レベルを表示される BT.2020 の線形な光の量にする。 1 を 100 cd/m² とする。
+	/// HLG は公称の最大輝度 1000 cd/m² の OOTF (γ=1.2) による。
+	pub fn display_light(&self,(r,g,b):Level) -> Level {
+		let (r,g,b) = (self.decode(r),self.decode(g),self.decode(b));
+		match self {
+			Transfer::Pq  => (r/100.0,g/100.0,b/100.0),
+			Transfer::Hlg => {
+				let ys = (0.2627*r+0.6780*g+0.0593*b).max(0.0);
+				let k = 1000.0*ys.powf(0.2)/100.0;
+				(r*k,g*k,b*k)
+			}
+		}
+	}
+
+	/// 基準白と同じ明るさの BT.709 の色域のカラーバーを BT.2020 の信号レベルにする。
+	fn bt709_bar(&self,i:usize) -> Level {
+		let (r,g,b) = bt709_to_bt2020(bar(i,self.decode(self.reference())));
+		(self.encode(r),self.encode(g),self.encode(b))
+	}
+
+}
+
+impl HdPatch {
+
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			HdPatch::PlusI    => "plus-i",
+			HdPatch::MinusI   => "minus-i",
+			HdPatch::White75  => "white75",
+			HdPatch::White100 => "white100"
+		}
+	}
+
+	pub fn next(&self) -> HdPatch {
+		match self {
+			HdPatch::PlusI    => HdPatch::MinusI,
+			HdPatch::MinusI   => HdPatch::White75,
+			HdPatch::White75  => HdPatch::White100,
+			HdPatch::White100 => HdPatch::PlusI
+		}
+	}
+
+}
+
+/// 正規化された座標を図の左上を原点とし、幅と高さを 1 とした座標に変換する。
+fn colorbar_coord(c:&CF,s:&CU,cbs:&ColorbarStatus) -> Option<CF> {
+	rationalize_coord(
+		c,s,
 		match cbs.mode {
-			CBSMode::SMPTE => ( 4,3),
-			CBSMode::ARIB  => (16,9)
+			CBSMode::SMPTE|CBSMode::EBU{..} => ( 4,3),
+			CBSMode::ARIB|CBSMode::RP219(_)|CBSMode::HDR(_) => (16,9)
 		},
 		match cbs.repeat {
 			false => RCOverflow::Discard,
 			true  => RCOverflow::Repeat
 		},
 		RCOrigin::TopLeft
-	);
-	if p_opt==None { return C::None }
-	let p = p_opt.unwrap();
+	)
+}
 
+/// 正規化された座標の位置のパッチの信号レベル
+/// 図の外では None を返す。 HDR の階段とランプは levels により範囲を変える。
+pub fn colorbar_level(c:&CF,s:&CU,cbs:&ColorbarStatus,levels:VL) -> Option<Level> {
+	let p = colorbar_coord(c,s,cbs)?;
 	match cbs.mode {
-		CBSMode::SMPTE => smpte_fragment(p),
-		CBSMode::ARIB  =>  arib_fragment(p)
+		CBSMode::HDR(tf) => Some(hdr_level(p,tf,levels)),
+		CBSMode::SMPTE => smpte_level(p),
+		CBSMode::RP219(patch)  => rp219_level(p,patch),
+		CBSMode::ARIB  => arib_level(p),
+		CBSMode::EBU{full,split} => ebu_level(p,full,split)
+	}
+}
+
+/// 信号レベルを狭い範囲の Y'CbCr の符号値 (8bit では Y' が 16-235、 Cb と Cr が 16-240) にする。
+/// 符号値はタイミング基準信号に予約された値を除いた範囲 (8bit では 1-254、 10bit では 4-1019) に収める。
+pub fn ycbcr_codes((r,g,b):Level,(kr,kb):(f64,f64),bits:u8) -> (u16,u16,u16) {
+	let scale = (1u32<<(bits-8)) as f64;
+	let code = |v:f64| (v*scale).round().clamp(scale,255.0*scale-1.0) as u16;
+	let y = kr*r+(1.0-kr-kb)*g+kb*b;
+	let cb = (b-y)/(2.0*(1.0-kb));
+	let cr = (r-y)/(2.0*(1.0-kr));
+	(code(16.0+219.0*y),code(128.0+224.0*cb),code(128.0+224.0*cr))
+}
+
+/// 灰色の信号レベル
+fn grey(v:f64) -> Level {
+	(v,v,v)
+}
+
+/// 100% のバーの色 (白、黄、シアン、緑、マゼンタ、赤、青、黒) を v 倍した信号レベル
+fn bar(i:usize,v:f64) -> Level {
+	let on = |bit:usize| if (i>>bit)&1==0 { v } else { 0.0 };
+	match i {
+		0..=7 => (on(1),on(2),on(0)),
+		_ => grey(0.0)
 	}
+}
+
+/// NTSC の I 軸 (123°) と Q 軸 (33°) の成分 (振幅は白を 1 とする) を持つ、輝度 0 の信号レベル
+/// 色差を U, V の重みで戻し、緑は輝度の係数 (Kr, Kb) で求める。
+fn iq_level(i:f64,q:f64,(kr,kb):(f64,f64)) -> Level {
+	let angle = 33f64.to_radians();
+	let u = -i*angle.sin()+q*angle.cos();
+	let v =  i*angle.cos()+q*angle.sin();
+	let (r,b) = (v/0.877,u/0.492);
+	(r,-(kr*r+kb*b)/(1.0-kr-kb),b)
+}
+
+/// SMPTE RP 219 の 16:9 の HD カラーバー
+/// 両端の幅 d を 1/8、中央のバーの幅 c を 3/4 の 1/7 とし、高さは上から 7/12, 1/12, 1/12, 3/12 の4段とする。
+fn rp219_level(p:CF,patch:HdPatch) -> Option<Level> {
+
+	let d = 1.0/8.0;
+	let c = 3.0/4.0/7.0;
+	// 中央のバーの幅を単位とした横の位置
+	let x = (p.0-d)/c;
+	let side = |left:Level,right:Level| if p.0<d { Some(left) } else if p.0>=1.0-d { Some(right) } else { None };
+
+	let level = match (p.1*12.0) as u8 {
+		0..=6 => side(grey(0.4),grey(0.4)).unwrap_or(bar(x as usize,0.75)),
+		7 => side(bar(2,1.0),bar(6,1.0)).unwrap_or(match x<1.0 {
+			true => match patch {
+				HdPatch::PlusI    => iq_level( 0.2,0.0,CBSMode::RP219(patch).luma()),
+				HdPatch::MinusI   => iq_level(-0.2,0.0,CBSMode::RP219(patch).luma()),
+				HdPatch::White75  => grey(0.75),
+				HdPatch::White100 => grey(1.0)
+			},
+			false => grey(0.75)
+		}),
+		8 => side(bar(1,1.0),bar(5,1.0)).unwrap_or(match x<1.0 {
+			true  => grey(0.0),
+			false => grey((x-1.0)/6.0)
+		}),
+		_ => side(grey(0.15),grey(0.15)).unwrap_or(pluge(x,1.0))
+	};
+
+	Some(level)
 
 }
 
-fn smpte_fragment(p:CF) -> C {
+/// RP 219 の Pattern 4 の中央
+/// 中央のバーの幅を単位とした横の位置 x に、黒、白 (white)、黒と -2%, 0%, +2%, 0%, +4% の PLUGE、黒を並べる。
+fn pluge(x:f64,white:f64) -> Level {
+	match x {
+		x if x<1.5       => grey(0.0),
+		x if x<3.5       => grey(white),
+		x if x<5.0-2.0/3.0 => grey(0.0),
+		x if x<5.0-1.0/3.0 => grey(-0.02),
+		x if x<5.0       => grey(0.0),
+		x if x<5.0+1.0/3.0 => grey(0.02),
+		x if x<5.0+2.0/3.0 => grey(0.0),
+		x if x<6.0       => grey(0.04),
+		_                => grey(0.0)
+	}
+}
+
+/// ITU-R BT.2111 の HDR のカラーバー
+/// 上から基準白 (PQ は 58%、 HLG は 75%) のカラーバー、 100% のカラーバー、基準白と同じ明るさの BT.709 の色域のカラーバー、
+/// 10% ごとの階段、ランプ、 PLUGE とし、高さは 6/12, 1/12, 1/12, 1/12, 1/12, 2/12 とする。
+/// limited (狭い範囲) では階段とランプに黒より下の -7% と白より上の 109% を含める。
+fn hdr_level(p:CF,tf:Transfer,levels:VL) -> Level {
+
+	let d = 1.0/8.0;
+	let c = 3.0/4.0/7.0;
+	let x = (p.0-d)/c;
+	let side = p.0<d || p.0>=1.0-d;
+	let i = (x.max(0.0) as usize).min(6);
+
+	match ((p.1*12.0) as u8,side) {
+		(0..=5,true)  => grey(0.4),
+		(0..=5,false) => bar(i,tf.reference()),
+		(6,true)      => grey(0.0),
+		(6,false)     => bar(i,1.0),
+		(7,true)      => grey(0.0),
+		(7,false)     => tf.bt709_bar(i),
+		(8,_) => match levels {
+			VL::Full => grey((((p.0*11.0) as usize).min(10) as f64)/10.0),
+			VL::Limited => match ((p.0*13.0) as usize).min(12) {
+				0  => grey(-0.07),
+				12 => grey(1.09),
+				n  => grey(((n-1) as f64)/10.0)
+			}
+		},
+		(9,_) => match levels {
+			VL::Full    => grey(p.0),
+			VL::Limited => grey(-0.07+1.16*p.0)
+		},
+		(_,true)  => grey(0.0),
+		(_,false) => pluge(x,tf.reference())
+	}
+
+}
+
+/// EBU の8本のカラーバー (白、黄、シアン、緑、マゼンタ、赤、青、黒)
+/// 白は常に 100% とする。 split では下半分を BT.601 の輝度の係数による同じ輝度の灰色にする。
+fn ebu_level(p:CF,full:bool,split:bool) -> Option<Level> {
+
+	let (kr,kb) = CBSMode::EBU{full,split}.luma();
+
+	let i = ((p.0*8.0) as usize).min(7);
+	let (r,g,b) = match i {
+		0 => grey(1.0),
+		_ => bar(i,if full { 1.0 } else { 0.75 })
+	};
+
+	match split && p.1>=0.5 {
+		true  => Some(grey(kr*r+(1.0-kr-kb)*g+kb*b)),
+		false => Some((r,g,b))
+	}
+
+}
+
+/// SMPTE EG 1 のカラーバー
+/// 上から 75% のカラーバー、逆順の色と黒を交互に並べた帯、 -I, 100% の白, +Q, 黒と -4%, 0%, +4% の PLUGE の3段とする。
+fn smpte_level(p:CF) -> Option<Level> {
 
 	// mapped position
 	let mp:(u8,u8) = match (p.1*12.0) as u8 {
@@ -42,49 +338,49 @@ fn smpte_fragment(p:CF) -> C {
 				0..=4 => (xs*4.0/5.0) as u8,
 				5     => ((xs-5.0)*3.0) as u8 + 4,
 				6     => 7,
-				_     => { return C::None }
+				_     => { return None }
 			};
 			(xn,2)
 		},
-		_ => { return C::None }
+		_ => { return None }
 	};
 
+	let luma = CBSMode::SMPTE.luma();
 	match mp {
-		(0,0)|(6,1)       => C::Int{r:204,g:204,b:204,a:255},
-		(1,0)             => C::Int{r:255,g:255,b:  0,a:255},
-		(2,0)|(4,1)       => C::Int{r:  0,g:255,b:255,a:255},
-		(3,0)             => C::Int{r:  0,g:255,b:  1,a:255},
-		(4,0)|(2,1)       => C::Int{r:255,g:  0,b:254,a:255},
-		(5,0)             => C::Int{r:254,g:  0,b:  0,a:255},
-		(6,0)|(0,1)       => C::Int{r:  0,g:  0,b:254,a:255},
-		(1,1)|(3,1)|(5,1) => C::Int{r: 19,g: 19,b: 19,a:255},
-		(0,2)             => C::Int{r:  8,g: 62,b: 90,a:255},
-		(1,2)             => C::Int{r:255,g:255,b:255,a:255},
-		(2,2)             => C::Int{r: 59,g:  0,b:126,a:255},
-		(4,2)             => C::Int{r:  0,g:  0,b:  0,a:255},
-		(3,2)|(5,2)|(7,2) => C::Int{r: 19,g: 19,b: 19,a:255},
-		(6,2)             => C::Int{r: 38,g: 38,b: 38,a:255},
-		_ => C::None
+		(0..=6,0)         => Some(bar(mp.0 as usize,0.75)),
+		(0,1)             => Some(bar(6,0.75)),
+		(2,1)             => Some(bar(4,0.75)),
+		(4,1)             => Some(bar(2,0.75)),
+		(6,1)             => Some(bar(0,0.75)),
+		(1|3|5,1)         => Some(grey(0.0)),
+		(0,2)             => Some(iq_level(-0.2,0.0,luma)),
+		(1,2)             => Some(grey(1.0)),
+		(2,2)             => Some(iq_level(0.0,0.2,luma)),
+		(4,2)             => Some(grey(-0.04)),
+		(6,2)             => Some(grey(0.04)),
+		(3|5|7,2)         => Some(grey(0.0)),
+		_ => None
 	}
 
 }
 
-fn arib_fragment(p:CF) -> C {
+/// ARIB STD-B28 のカラーバー
+fn arib_level(p:CF) -> Option<Level> {
 
 	// mapped position
 	let mut mp:(u8,u8,f64) = (
 		match p.0 {
 			x if x> 0.0      && x<=(1.0/8.0) => 0,
-			x if x< 1.0      && x>=(7.0/8.0) => 1,
+			x if ((7.0/8.0)..1.0).contains(&x) => 1,
 			x if x>(1.0/8.0) && x< (7.0/8.0) => 2,
-			_ => { return C::None }
+			_ => { return None }
 		},
 		match (p.1*12.0) as u8 {
 			0..=6  => 0,
 			7      => 1,
 			8      => 2,
 			9..=12 => 3,
-			_      => { return C::None }
+			_      => { return None }
 		},
 		0.0
 	);
@@ -99,51 +395,220 @@ fn arib_fragment(p:CF) -> C {
 				// n等分していきながら分岐
 				let xs = mp.2*7.0;
 				mp.0 = match xs as u8 {
-					0|1|2|3 => {
+					0..=3 => {
 						match (xs*2.0) as u8 {
-							0|1|2   => 2,
-							3|4|5|6 => 3,
+							0..=2   => 2,
+							3..=6   => 3,
 							7       => 4,
-							_       => { return C::None }
+							_       => { return None }
 						}
 					},
 					4|5 => {
 						((xs-4.0)*3.0) as u8 + 4
 					},
 					6 => 10,
-					_ => { return C::None }
+					_ => { return None }
 				};
 			},
 			2 => {},
-			_ => { return C::None }
+			_ => { return None }
 		}
 	}
 
 	match mp {
 		// 両端
-		(0|1,0,_)       => C::Float{r:0.4 ,g:0.4 ,b:0.4 ,a:1.0},
-		(0  ,1,_)       => C::Float{r:0.0 ,g:1.0 ,b:1.0 ,a:1.0},
-		(1  ,1,_)       => C::Float{r:0.0 ,g:0.0 ,b:1.0 ,a:1.0},
-		(0  ,2,_)       => C::Float{r:1.0 ,g:1.0 ,b:0.0 ,a:1.0},
-		(1  ,2,_)       => C::Float{r:1.0 ,g:0.0 ,b:0.0 ,a:1.0},
-		(0|1,3,_)       => C::Float{r:0.15,g:0.15,b:0.15,a:1.0},
+		(0|1,0,_)       => Some(grey(0.4)),
+		(0  ,1,_)       => Some(bar(2,1.0)),
+		(1  ,1,_)       => Some(bar(6,1.0)),
+		(0  ,2,_)       => Some(bar(1,1.0)),
+		(1  ,2,_)       => Some(bar(5,1.0)),
+		(0|1,3,_)       => Some(grey(0.15)),
 		// 中央
-		(2,0,_)|(3,1,_) => C::Float{r:0.75,g:0.75,b:0.75,a:1.0},
-		(3,0,_)         => C::Float{r:0.75,g:0.75,b:0.0 ,a:1.0},
-		(4,0,_)         => C::Float{r:0.0 ,g:0.75,b:0.75,a:1.0},
-		(5,0,_)         => C::Float{r:0.0 ,g:0.75,b:0.0 ,a:1.0},
-		(6,0,_)         => C::Float{r:0.75,g:0.0 ,b:0.75,a:1.0},
-		(7,0,_)         => C::Float{r:0.75,g:0.0 ,b:0.0 ,a:1.0},
-		(8,0,_)         => C::Float{r:0.0 ,g:0.0 ,b:0.75,a:1.0},
-		(2,1,_)         => C::Float{r:1.0 ,g:1.0 ,b:1.0 ,a:1.0},
-		(2,2,d)         => C::GFloat{v:d,a:1.0},
-		(2|4|8|10,3,_)  => C::Float{r:0.0 ,g:0.0 ,b:0.0 ,a:1.0},
-		(3       ,3,_)  => C::Float{r:1.0 ,g:1.0 ,b:1.0 ,a:1.0},
-		(5       ,3,_)  => C::Float{r:0.0 ,g:0.0 ,b:0.0 ,a:1.0}, // actually -2 %
-		(6       ,3,_)  => C::Float{r:0.0 ,g:0.0 ,b:0.0 ,a:1.0},
-		(7       ,3,_)  => C::Float{r:0.02,g:0.02,b:0.02,a:1.0},
-		(9       ,3,_)  => C::Float{r:0.04,g:0.04,b:0.04,a:1.0},
-		_ => C::None
-	}
-
-}
\ No newline at end of file
+		(2..=8,0,_)     => Some(bar((mp.0-2) as usize,0.75)),
+		(3,1,_)         => Some(grey(0.75)),
+		(2,1,_)         => Some(grey(1.0)),
+		(2,2,d)         => Some(grey(d)),
+		(2|4|6|8|10,3,_) => Some(grey(0.0)),
+		(3       ,3,_)  => Some(grey(1.0)),
+		(5       ,3,_)  => Some(grey(-0.02)),
+		(7       ,3,_)  => Some(grey(0.02)),
+		(9       ,3,_)  => Some(grey(0.04)),
+		_ => None
+	}
+
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	const HD:(f64,f64) = (0.2126,0.0722);
+
+	/// RP 219 の図で、中央のバーの幅を単位とした横の位置 x、上から row 段目 (高さの 1/12 単位) の座標
+	fn rp219_at(x:f64,row:u8) -> CF {
+		(1.0/8.0+x*3.0/4.0/7.0,(row as f64+0.5)/12.0)
+	}
+
+	fn y_codes(p:CF,patch:HdPatch) -> (u16,u16) {
+		let level = rp219_level(p,patch).unwrap();
+		(ycbcr_codes(level,HD,10).0,ycbcr_codes(level,HD,8).0)
+	}
+
+	/// 信号レベルを 8bit の R'G'B' の画素値にする。
+	fn rgb8(v:f64,levels:VL) -> i32 {
+		(levels.encode(v)*255.0).round() as i32
+	}
+
+	#[test]
+	fn rp219_white_and_black() {
+		assert_eq!(y_codes(rp219_at(0.5,0),HdPatch::PlusI),(721,180));
+		assert_eq!(ycbcr_codes(grey(0.75),HD,10),(721,512,512));
+		assert_eq!(y_codes(rp219_at(0.5,7),HdPatch::White75),(721,180));
+		assert_eq!(y_codes(rp219_at(0.5,7),HdPatch::White100),(940,235));
+		assert_eq!(y_codes(rp219_at(0.5,8),HdPatch::PlusI),(64,16));
+		assert_eq!(y_codes((0.05,0.1),HdPatch::PlusI),(414,104));
+		assert_eq!(y_codes((0.05,0.95),HdPatch::PlusI),(195,49));
+	}
+
+	#[test]
+	fn rp219_bars() {
+		// 75% の黄、シアン、青 (RP 219 の表の 10bit の符号値)
+		assert_eq!(ycbcr_codes(rp219_level(rp219_at(1.5,0),HdPatch::PlusI).unwrap(),HD,10),(674,176,543));
+		assert_eq!(ycbcr_codes(rp219_level(rp219_at(2.5,0),HdPatch::PlusI).unwrap(),HD,10),(581,589,176));
+		assert_eq!(ycbcr_codes(rp219_level(rp219_at(6.5,0),HdPatch::PlusI).unwrap(),HD,10),(111,848,481));
+		// 両端の 100% の黄と赤
+		assert_eq!(ycbcr_codes(rp219_level((0.05,8.5/12.0),HdPatch::PlusI).unwrap(),HD,10),(877,64,553));
+		assert_eq!(ycbcr_codes(rp219_level((0.95,8.5/12.0),HdPatch::PlusI).unwrap(),HD,10),(250,409,960));
+	}
+
+	#[test]
+	fn rp219_pluge() {
+		let at = |x:f64| y_codes(rp219_at(x,11),HdPatch::PlusI);
+		assert_eq!(at(0.5),(64,16));
+		assert_eq!(at(2.5),(940,235));
+		assert_eq!(at(5.0-0.5),(46,12));
+		assert_eq!(at(5.0-1.0/6.0),(64,16));
+		assert_eq!(at(5.0+1.0/6.0),(82,20));
+		assert_eq!(at(5.0+0.5),(64,16));
+		assert_eq!(at(5.0+5.0/6.0),(99,25));
+		assert_eq!(pluge(4.5,0.58),grey(-0.02));
+		assert_eq!(pluge(2.0,0.58),grey(0.58));
+	}
+
+	#[test]
+	fn rgb_levels() {
+		// limited は黒を 16、白を 235 とし、黒より下の信号も残す。 full は 0 から 255 とする。
+		for (v,full,limited) in [(0.0,0,16),(1.0,255,235),(0.75,191,180),(-0.02,-5,12),(0.04,10,25)] {
+			assert_eq!(rgb8(v,VL::Full),full);
+			assert_eq!(rgb8(v,VL::Limited),limited);
+		}
+		// 16bit の limited は 8bit の符号値を 256 倍した値、 10bit の符号値の 64 倍とする
+		assert_eq!((VL::Limited.encode(0.75)*65280.0).round() as u32,721*64);
+		assert_eq!((VL::Limited.encode(1.0)*65280.0).round() as u32,940*64);
+		assert_eq!((VL::Full.encode(0.75)*65535.0).round() as u32,49151);
+		for v in [-0.07,0.0,0.5,1.09] {
+			assert!((VL::Limited.decode(VL::Limited.encode(v))-v).abs()<1e-12);
+		}
+	}
+
+	#[test]
+	fn ycbcr_reserved_codes() {
+		assert_eq!(ycbcr_codes(grey(-1.0),HD,10).0,4);
+		assert_eq!(ycbcr_codes(grey(2.0),HD,10).0,1019);
+		assert_eq!(ycbcr_codes(grey(-1.0),HD,8).0,1);
+		assert_eq!(ycbcr_codes(grey(2.0),HD,8).0,254);
+	}
+
+	#[test]
+	fn hdr_reference_white() {
+		// 基準白 203 cd/m² は PQ で 58%、 HLG で 75% の信号レベルになる
+		let top = (0.2,0.1);
+		assert_eq!(hdr_level(top,Transfer::Pq,VL::Limited),grey(0.58));
+		assert_eq!(hdr_level(top,Transfer::Hlg,VL::Limited),grey(0.75));
+		assert!((Transfer::Pq.display_light(grey(0.58)).0-2.03).abs()<0.02);
+		assert!((Transfer::Hlg.display_light(grey(0.75)).0-2.03).abs()<0.02);
+		// BT.709 の色域のバーの白は基準白のまま
+		let (r,g,b) = Transfer::Pq.bt709_bar(0);
+		assert!((r-0.58).abs()<1e-3 && (g-0.58).abs()<1e-3 && (b-0.58).abs()<1e-3);
+	}
+
+	#[test]
+	fn hdr_stairs() {
+		let step = |x:f64,levels:VL| hdr_level((x,8.5/12.0),Transfer::Pq,levels).0;
+		assert_eq!(step(0.01,VL::Full),0.0);
+		assert_eq!(step(0.99,VL::Full),1.0);
+		assert_eq!(step(0.01,VL::Limited),-0.07);
+		assert_eq!(step(0.99,VL::Limited),1.09);
+		assert_eq!(ycbcr_codes(grey(-0.07),CBSMode::HDR(Transfer::Pq).luma(),10).0,4);
+		assert_eq!(ycbcr_codes(grey(1.09),CBSMode::HDR(Transfer::Pq).luma(),10).0,1019);
+	}
+
+	const SD:(f64,f64) = (0.299,0.114);
+
+	#[test]
+	fn ebu_codes() {
+		let at = |i:usize,full:bool,split:bool,y:f64| ycbcr_codes(ebu_level(((i as f64+0.5)/8.0,y),full,split).unwrap(),SD,8);
+		assert_eq!(at(0,false,false,0.5),(235,128,128));
+		assert_eq!(at(1,false,false,0.5),(162,44,142));
+		assert_eq!(at(2,false,false,0.5),(131,156,44));
+		assert_eq!(at(6,false,false,0.5),(35,212,114));
+		assert_eq!(at(7,false,false,0.5),(16,128,128));
+		assert_eq!(at(1,true,false,0.5),(210,16,146));
+		assert_eq!(at(5,true,false,0.5),(81,90,240));
+		// スプリットフィールドの下半分は同じ輝度の灰色
+		assert_eq!(at(1,false,true,0.25),(162,44,142));
+		assert_eq!(at(1,false,true,0.75),(162,128,128));
+		assert_eq!(at(6,true,true,0.75),(41,128,128));
+	}
+
+	#[test]
+	fn smpte_codes() {
+		let at = |x:f64,row:u8| ycbcr_codes(smpte_level((x/7.0,(row as f64+0.5)/12.0)).unwrap(),SD,8);
+		assert_eq!(at(0.5,0),(180,128,128));
+		assert_eq!(at(1.5,0),(162,44,142));
+		assert_eq!(at(4.5,0),(84,184,198));
+		assert_eq!(at(6.5,0),(35,212,114));
+		// 逆順の帯
+		assert_eq!(at(0.5,8),(35,212,114));
+		assert_eq!(at(1.5,8),(16,128,128));
+		assert_eq!(at(6.5,8),(180,128,128));
+		// -I, 100% の白, +Q, 黒と PLUGE
+		assert_eq!(at(0.5,10),(16,156,97));
+		assert_eq!(at(2.0,10),(235,128,128));
+		assert_eq!(at(3.0,10),(16,171,148));
+		assert_eq!(at(4.5,10),(16,128,128));
+		assert_eq!(at(5.0+1.0/6.0,10),(7,128,128));
+		assert_eq!(at(5.5,10),(16,128,128));
+		assert_eq!(at(5.0+5.0/6.0,10),(25,128,128));
+		assert_eq!(at(6.5,10),(16,128,128));
+		assert_eq!(ycbcr_codes(smpte_level((0.5/7.0,10.5/12.0)).unwrap(),SD,10),(64,624,390));
+	}
+
+	#[test]
+	fn arib_codes() {
+		// 中央の幅 3/4 を 7 等分した位置 x (0 から 7) の座標
+		let at = |x:f64,row:u8| ycbcr_codes(arib_level((1.0/8.0+x*3.0/4.0/7.0,(row as f64+0.5)/12.0)).unwrap(),HD,10);
+		assert_eq!(at(0.5,0),(721,512,512));
+		assert_eq!(at(1.5,0),(674,176,543));
+		assert_eq!(at(4.5,0),(251,771,817));
+		assert_eq!(at(6.5,0),(111,848,481));
+		assert_eq!(at(-0.5,0),(414,512,512));
+		assert_eq!(at(-0.5,7),(754,615,64));
+		assert_eq!(at(7.5,7),(127,960,471));
+		assert_eq!(at(0.5,7),(940,512,512));
+		assert_eq!(at(3.5,7),(721,512,512));
+		assert_eq!(at(-0.5,8),(877,64,553));
+		assert_eq!(at(7.5,8),(250,409,960));
+		assert_eq!(at(0.05,8),(70,512,512));
+		assert_eq!(at(3.5,8),(502,512,512));
+		assert_eq!(at(-0.5,11),(195,512,512));
+		assert_eq!(at(0.5,11),(64,512,512));
+		assert_eq!(at(2.5,11),(940,512,512));
+		assert_eq!(at(4.5,11),(46,512,512));
+		assert_eq!(at(5.0+1.0/6.0,11),(82,512,512));
+		assert_eq!(at(5.0+5.0/6.0,11),(99,512,512));
+		assert_eq!(at(6.5,11),(64,512,512));
+	}
+
+}
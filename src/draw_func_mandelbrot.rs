@@ -1,20 +1,444 @@
+use std::sync::OnceLock;
+use num::Float;
 use crate::draw_lib::*;
+use crate::draw_lanes::*;
+use crate::bigfixed::BigFixed;
 
-pub fn mandelbrot_fragment(c:&CF,s:&CU) -> C {
+/// この拡大率以上では参照軌道からの摂動により計算する。
+pub const DEEP_ZOOM:f64 = 1e12;
 
-	let p = rationalize_coord(c,&s,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
+/// 拡大率の上限。 f64 の摂動が表現できる範囲に収める。
+const MAX_ZOOM:f64 = 1e300;
 
-	let c = Z { re: p.0-0.5, im: p.1 };
+/// |z| < |Z| × GLITCH となった点はグリッチとみなし、参照軌道を取り直す。
+const GLITCH:f64 = 1e-3;
 
-	let mut z = Z{ re:0.0, im:0.0 };
-	for n in 0..200 {
+/// 距離推定で使う発散の判定の半径
+const DE_BAILOUT:f64 = 1e3;
+
+/// 発散したイテレート回数と距離推定値 (距離推定を使わない場合は 0)
+/// 発散しなかった点は None になる。
+type Escape = Option<(usize,f64)>;
+
+impl MandelbrotStatus {
+
+	/// 中心を10進数の文字列で指定して生成する。中心が数値として読めない場合は None を返す。
+	pub fn new(re:&str,im:&str,zoom:f64,max:usize,distance:bool) -> Option<MandelbrotStatus> {
+		let zoom = zoom.clamp(f64::MIN_POSITIVE,MAX_ZOOM);
+		let frac = frac_limbs(zoom);
+		let mut ms = MandelbrotStatus {
+			re: BigFixed::parse(re,frac)?,
+			im: BigFixed::parse(im,frac)?,
+			center: (0.0,0.0),
+			zoom,
+			max: max.max(1),
+			distance,
+			orbit: OnceLock::new()
+		};
+		ms.update();
+		Some(ms)
+	}
+
+	/// 参照軌道を使う深い拡大かどうか
+	pub fn deep(&self) -> bool {
+		self.zoom>=DEEP_ZOOM
+	}
+
+	/// 拡大率を factor 倍にする。
+	pub fn zoom_by(&mut self,factor:f64) {
+		self.zoom = (self.zoom*factor).clamp(0.25,MAX_ZOOM);
+		let frac = frac_limbs(self.zoom).max(self.re.frac_limbs());
+		self.re = self.re.resize(frac);
+		self.im = self.im.resize(frac);
+		self.update();
+	}
+
+	/// 表示範囲の短辺を2とした単位で中心を移動する。
+	pub fn pan(&mut self,dx:f64,dy:f64) {
+		let frac = self.re.frac_limbs();
+		self.re = &self.re + &BigFixed::from_f64(dx/self.zoom,frac);
+		self.im = &self.im + &BigFixed::from_f64(dy/self.zoom,frac);
+		self.update();
+	}
+
+	pub fn set_max(&mut self,max:usize) {
+		self.max = max;
+		self.update();
+	}
+
+	/// 中心の近似値を更新し、参照軌道を破棄する。
+	fn update(&mut self) {
+		self.center = (self.re.to_f64(),self.im.to_f64());
+		self.orbit = OnceLock::new();
+	}
+
+	/// 中心の参照軌道 Z_0, Z_1, … を多倍長で計算し、 f64 に丸めて返す。
+	/// 発散するか最大イテレート回数に達した時点で打ち切る。
+	fn reference_orbit(&self) -> &[CF] {
+		self.orbit.get_or_init(|| {
+			let frac = self.re.frac_limbs();
+			let mut zr = BigFixed::zero(frac);
+			let mut zi = BigFixed::zero(frac);
+			let mut orbit = vec![(0.0,0.0)];
+			for _ in 0..self.max {
+				let zr2 = &zr*&zr;
+				let zi2 = &zi*&zi;
+				let zri = &zr*&zi;
+				zr = &(&zr2-&zi2)+&self.re;
+				zi = &(&zri+&zri)+&self.im;
+				let z = (zr.to_f64(),zi.to_f64());
+				orbit.push(z);
+				if z.0*z.0+z.1*z.1>4.0 { break; }
+			}
+			orbit
+		})
+	}
+
+}
+
+/// 拡大率に対して必要な小数部の limb の数
+/// 画素の間隔より64bit以上細かい精度を確保する。
+fn frac_limbs(zoom:f64) -> usize {
+	((zoom.log2().max(0.0)+96.0)/32.0).ceil() as usize
+}
+
+pub fn mandelbrot_fragment(c:&CF,s:&CU,ms:&MandelbrotStatus,pr:PR,pal:Option<&PaletteStatus>) -> C {
+	let pixel = pixel_size(s,ms);
+	if ms.deep() {
+		return mandelbrot_color(mandelbrot_perturbed(c,s,ms),ms,pixel,pal);
+	}
+	precision_colors(
+		pr,
+		|| vec![mandelbrot_escape::<f64>(c,s,ms)],
+		|| vec![mandelbrot_escape::<f32>(c,s,ms)],
+		|a,b| mandelbrot_same(a,b,ms,pixel),
+		|e| mandelbrot_color(e,ms,pixel,pal)
+	).pop().unwrap()
+}
+
+/// 複数の点を LANES 個ずつまとめて計算する。結果は mandelbrot_fragment と一致する。
+/// 参照軌道を使う場合は1点ずつ計算する。
+pub fn mandelbrot_fragments(cs:&[CF],s:&CU,ms:&MandelbrotStatus,pr:PR,pal:Option<&PaletteStatus>) -> Vec<C> {
+	let pixel = pixel_size(s,ms);
+	if ms.deep() {
+		return cs.iter().map(|c| mandelbrot_color(mandelbrot_perturbed(c,s,ms),ms,pixel,pal)).collect();
+	}
+	precision_colors(
+		pr,
+		|| mandelbrot_escapes::<f64>(cs,s,ms),
+		|| mandelbrot_escapes::<f32>(cs,s,ms),
+		|a,b| mandelbrot_same(a,b,ms,pixel),
+		|e| mandelbrot_color(e,ms,pixel,pal)
+	)
+}
+
+/// 表示範囲の中心からの相対位置
+fn mandelbrot_offset(c:&CF,s:&CU,ms:&MandelbrotStatus) -> CF {
+	let p = rationalize_coord(c,s,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
+	(p.0/ms.zoom,p.1/ms.zoom)
+}
+
+/// 複素平面上の位置
+pub fn mandelbrot_position<F:Float>(c:&CF,s:&CU,ms:&MandelbrotStatus) -> (F,F) {
+	let d = mandelbrot_offset(c,s,ms);
+	(F::from(d.0+ms.center.0).unwrap(),F::from(d.1+ms.center.1).unwrap())
+}
+
+/// 複素平面上での1ピクセルの大きさ
+fn pixel_size(s:&CU,ms:&MandelbrotStatus) -> f64 {
+	2.0/(s.0.min(s.1).max(1) as f64)/ms.zoom
+}
+
+/// 主カージオイドまたは周期2の円板の内側にあるかどうか
+/// これらの点は発散しないため、反復せずに集合の内側とみなせる。
+fn in_main_bulbs<F:Float>(cr:F,ci:F) -> bool {
+	let quarter = F::from(0.25).unwrap();
+	let x = cr-quarter;
+	let q = x*x+ci*ci;
+	let y = cr+F::one();
+	q*(q+x) <= ci*ci*quarter || y*y+ci*ci <= F::from(0.0625).unwrap()
+}
+
+/// 発散の判定に使う半径
+/// 距離推定では対数の精度を確保するため、十分に大きくとる。
+fn bailout<F:Float>(ms:&MandelbrotStatus) -> F {
+	F::from(if ms.distance { DE_BAILOUT } else { 2.0 }).unwrap()
+}
+
+/// 周期検出で同じ点とみなす差
+fn period_tolerance<F:Float>() -> F {
+	F::epsilon()*F::from(4.0).unwrap()
+}
+
+/// 1点について発散したイテレート回数と距離推定値を返す。
+/// 主カージオイドと周期2の円板の内側、および軌道の周期を検出した点は集合の内側として打ち切る。
+/// 周期の検出では 1,2,4,… 回目の z を保存し、その後の z と比較する。
+fn mandelbrot_escape<F:Float>(c:&CF,s:&CU,ms:&MandelbrotStatus) -> Escape {
+
+	let p = mandelbrot_position::<F>(c,s,ms);
+	if in_main_bulbs(p.0,p.1) { return None; }
+
+	let c = Complex { re: p.0, im: p.1 };
+	let (one,two) = (F::one(),F::from(2.0).unwrap());
+	let r = bailout::<F>(ms);
+	let tol = period_tolerance::<F>();
+
+	let mut z = Complex { re:F::zero(), im:F::zero() };
+	let mut d = (F::zero(),F::zero());
+	let mut saved = z;
+	let mut check = 1;
+	for n in 0..ms.max {
+		if ms.distance {
+			d = (two*(z.re*d.0-z.im*d.1)+one,two*(z.re*d.1+z.im*d.0));
+		}
 		z = z.powi(2) + c;
-		if z.abs() > 2.0 {
-			let rgb = deg2rgb(240.0-(n as f64)/200.0*180.0);
-			return C::Float{r:rgb.0*0.6,g:rgb.1*0.6,b:rgb.2*0.6,a:1.0}
+		if z.norm() > r {
+			return Some((n,distance_estimate((z.re,z.im),d,ms)));
+		}
+		if (z.re-saved.re).abs()<=tol && (z.im-saved.im).abs()<=tol { return None; }
+		if n==check {
+			saved = z;
+			check *= 2;
+		}
+	}
+
+	None
+
+}
+
+fn mandelbrot_escapes<F:Float>(cs:&[CF],s:&CU,ms:&MandelbrotStatus) -> Vec<Escape> {
+
+	cs.chunks(LANES).flat_map(|chunk| {
+
+		let mut c:LZ<F> = lanes_zero();
+		for (i,coord) in chunk.iter().enumerate() {
+			(c.0[i],c.1[i]) = mandelbrot_position(coord,s,ms);
 		}
+
+		mandelbrot_lanes(&c,chunk.len(),ms).into_iter().take(chunk.len())
+
+	}).collect()
+
+}
+
+/// 先頭の active 個のレーンについて、 mandelbrot_escape と同じ計算をまとめて行う。
+/// 全てのレーンが発散するか内側と判定された時点で打ち切る。
+fn mandelbrot_lanes<F:Float>(c:&LZ<F>,active:usize,ms:&MandelbrotStatus) -> [Escape;LANES] {
+
+	let (one,two) = (F::one(),F::from(2.0).unwrap());
+	let r = bailout::<F>(ms);
+	let tol = period_tolerance::<F>();
+	let mut z:LZ<F> = lanes_zero();
+	let mut d:LZ<F> = lanes_zero();
+	let mut saved:LZ<F> = lanes_zero();
+	let mut check = 1;
+	let mut escaped = [None;LANES];
+	let mut done = [false;LANES];
+	for (i,d) in done.iter_mut().enumerate() {
+		*d = i>=active || in_main_bulbs(c.0[i],c.1[i]);
 	}
 
-	C::GFloat{v:0.0,a:1.0}
+	for n in 0..ms.max {
+
+		if done.iter().all(|d| *d) { break; }
+
+		if ms.distance {
+			for i in 0..LANES {
+				(d.0[i],d.1[i]) = (two*(z.0[i]*d.0[i]-z.1[i]*d.1[i])+one,two*(z.0[i]*d.1[i]+z.1[i]*d.0[i]));
+			}
+		}
+
+		z = lanes_mul(&z,&z);
+		for i in 0..LANES {
+			z.0[i] = z.0[i] + c.0[i];
+			z.1[i] = z.1[i] + c.1[i];
+		}
+
+		let gt = lanes_abs_gt(&z,r);
+		for i in 0..LANES {
+			if done[i] { continue; }
+			if gt[i] {
+				done[i] = true;
+				escaped[i] = Some((n,distance_estimate((z.0[i],z.1[i]),(d.0[i],d.1[i]),ms)));
+			}
+			else if (z.0[i]-saved.0[i]).abs()<=tol && (z.1[i]-saved.1[i]).abs()<=tol {
+				done[i] = true;
+			}
+		}
+
+		if n==check {
+			saved = z;
+			check *= 2;
+		}
+
+	}
+
+	escaped
+
+}
+
+/// 参照軌道 Z からの摂動 δ として1点を計算し、発散したイテレート回数と距離推定値を返す。
+/// δ_{n+1} = 2 Z_n δ_n + δ_n² + δc を f64 で計算する。
+/// |Z+δ| が |δ| より小さくなった場合、グリッチを検出した場合、参照軌道の終わりに達した場合は
+/// Z+δ を新たな δ として参照軌道の先頭から計算し直す。
+/// 画素の間隔が f64 の分解能を下回るため、周期の検出は行わない。
+/// 主カージオイドと周期2の円板の判定も、画素の間隔が中心の f64 での分解能より大きい場合だけ行う。
+fn mandelbrot_perturbed(c:&CF,s:&CU,ms:&MandelbrotStatus) -> Escape {
+
+	let orbit = ms.reference_orbit();
+	let dc = mandelbrot_offset(c,s,ms);
+	let ulp = f64::EPSILON*ms.center.0.abs().max(ms.center.1.abs()).max(1.0);
+	if pixel_size(s,ms)>ulp && in_main_bulbs(ms.center.0+dc.0,ms.center.1+dc.1) { return None; }
+
+	let r2 = bailout::<f64>(ms).powi(2);
+	let mut dz:CF = (0.0,0.0);
+	let mut d:CF = (0.0,0.0);
+	let mut m = 0;
+
+	for n in 0..ms.max {
+
+		let zm = orbit[m];
+		if ms.distance {
+			let z = (zm.0+dz.0,zm.1+dz.1);
+			d = (2.0*(z.0*d.0-z.1*d.1)+1.0,2.0*(z.0*d.1+z.1*d.0));
+		}
+		dz = (
+			2.0*(zm.0*dz.0-zm.1*dz.1) + (dz.0*dz.0-dz.1*dz.1) + dc.0,
+			2.0*(zm.0*dz.1+zm.1*dz.0) + 2.0*dz.0*dz.1 + dc.1
+		);
+		m += 1;
+
+		let zm = orbit[m];
+		let z = (zm.0+dz.0,zm.1+dz.1);
+		let z2 = z.0*z.0+z.1*z.1;
+		if z2>r2 { return Some((n,distance_estimate(z,d,ms))); }
+
+		let glitch = z2 < GLITCH*GLITCH*(zm.0*zm.0+zm.1*zm.1);
+		if glitch || z2<dz.0*dz.0+dz.1*dz.1 || m==orbit.len()-1 {
+			dz = z;
+			m = 0;
+		}
+
+	}
+
+	None
+
+}
+
+/// 発散した点の z と微分 dz/dc から集合の境界までの距離を推定する。距離推定を使わない場合は 0 とする。
+fn distance_estimate<F:Float>(z:(F,F),d:(F,F),ms:&MandelbrotStatus) -> f64 {
+	if !ms.distance { return 0.0; }
+	let z = z.0.to_f64().unwrap().hypot(z.1.to_f64().unwrap());
+	let d = d.0.to_f64().unwrap().hypot(d.1.to_f64().unwrap());
+	z*z.ln()/d
+}
+
+/// 距離推定による明るさ。境界から1ピクセル以上離れた点は白になる。
+fn distance_shade(de:f64,pixel:f64) -> f64 {
+	(de/pixel).clamp(0.0,1.0).sqrt()
+}
+
+/// 精度による結果の違いを判定する。
+/// イテレート回数による色分けでは回数が、距離推定では8bitに丸めた明るさが異なれば違うとみなす。
+fn mandelbrot_same(a:&Escape,b:&Escape,ms:&MandelbrotStatus,pixel:f64) -> bool {
+	match (a,b,ms.distance) {
+		(None,None,_) => true,
+		(Some((n1,_)),Some((n2,_)),false) => n1==n2,
+		(Some((_,d1)),Some((_,d2)),true) => {
+			let v = |d:f64| (distance_shade(d,pixel)*255.0).round();
+			v(*d1)==v(*d2)
+		},
+		_ => false
+	}
+}
+
+/// 発散したイテレート回数、または距離推定による色。発散しなかった点は黒。
+/// パレットが指定されていればイテレート回数の割合、または距離推定による明るさをパレットの位置とする。
+fn mandelbrot_color(escaped:Escape,ms:&MandelbrotStatus,pixel:f64,pal:Option<&PaletteStatus>) -> C {
+	let t = match (escaped,ms.distance) {
+		(Some((n,_)),false) => (n as f64)/(ms.max as f64),
+		(Some((_,de)),true) => distance_shade(de,pixel),
+		(None,_) => { return C::GFloat{v:0.0,a:1.0}; }
+	};
+	match (pal,ms.distance) {
+		(Some(pal),_) => {
+			let rgb = pal.color(t);
+			C::Float{r:rgb.0,g:rgb.1,b:rgb.2,a:1.0}
+		},
+		(None,false) => {
+			let rgb = deg2rgb(240.0-t*180.0);
+			C::Float{r:rgb.0*0.6,g:rgb.1*0.6,b:rgb.2*0.6,a:1.0}
+		},
+		(None,true) => C::GFloat{v:t,a:1.0}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	const SIZE:CU = (16,16);
+
+	/// 中心 0、拡大率 0.5 の表示で、複素平面上の点 (re,im) に当たる座標
+	fn coord(re:f64,im:f64) -> CF {
+		((re/2.0+1.0)/2.0,(1.0-im/2.0)/2.0)
+	}
+
+	/// 16 × 16 の格子点と、集合の境界、発散の判定の境目、最大イテレート回数に達する点
+	fn coords() -> Vec<CF> {
+		let mut cs:Vec<CF> = (0..SIZE.0*SIZE.1).map(|i| {
+			(((i%SIZE.0) as f64+0.5)/(SIZE.0 as f64),((i/SIZE.0) as f64+0.5)/(SIZE.1 as f64))
+		}).collect();
+		cs.extend([
+			coord(0.25,0.0),coord(-0.75,0.0),coord(-2.0,0.0),coord(0.0,1.0),coord(-0.125,0.75),
+			coord(2.0,0.0),coord(0.0,2.0),coord(-0.75,0.0625),coord(0.2501,0.0),coord(0.26,0.0)
+		]);
+		cs
+	}
+
+	fn status(distance:bool) -> MandelbrotStatus {
+		MandelbrotStatus::new("0","0",0.5,100,distance).unwrap()
+	}
+
+	#[test]
+	fn simd_matches_scalar() {
+		let cs = coords();
+		for distance in [false,true] {
+			let ms = status(distance);
+			for pr in [PR::F64,PR::F32] {
+				let simd = mandelbrot_fragments(&cs,&SIZE,&ms,pr,None);
+				for (c,v) in cs.iter().zip(simd) {
+					assert!(mandelbrot_fragment(c,&SIZE,&ms,pr,None)==v,"{:?} distance={} {}",c,distance,pr.keyword());
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn boundary_and_limit_points() {
+		let ms = status(false);
+		let escape = |re,im| mandelbrot_escape::<f64>(&coord(re,im),&SIZE,&ms).map(|e| e.0);
+		assert_eq!(escape(2.0,0.0),Some(1));
+		assert_eq!(escape(-2.0,0.0),None);
+		assert_eq!(escape(0.25,0.0),None);
+		assert_eq!(escape(0.2501,0.0),None);
+		assert!(escape(0.26,0.0).is_some());
+		for c in [coord(-2.0,0.0),coord(0.2501,0.0),coord(0.26,0.0)] {
+			for pr in [PR::F64,PR::F32] {
+				assert!(mandelbrot_fragment(&c,&SIZE,&ms,pr,None)==mandelbrot_fragments(&[c],&SIZE,&ms,pr,None)[0]);
+			}
+		}
+	}
+
+	#[test]
+	fn deep_zoom_skips_bulb_shortcut() {
+		// 中心の f64 での分解能より細かい拡大では、カージオイドの内側も摂動で計算する
+		let inside = MandelbrotStatus::new("-0.1","0.1",1e20,50,false).unwrap();
+		assert!(pixel_size(&SIZE,&inside)<f64::EPSILON);
+		assert_eq!(mandelbrot_perturbed(&(0.5,0.5),&SIZE,&inside),None);
+		let outside = MandelbrotStatus::new("0.3","0",1e20,50,false).unwrap();
+		assert!(mandelbrot_perturbed(&(0.5,0.5),&SIZE,&outside).is_some());
+	}
 
-}
\ No newline at end of file
+}
@@ -1,28 +1,199 @@
+use std::f64::consts::PI;
+use num::Float;
 use crate::draw_lib::*;
+use crate::draw_lanes::*;
 
-pub fn newton_fragment(c:&CF,s:&CU,nas:&NewtonApproxStatus) -> C {
+/// 収束したイテレート回数とその時点の偏角
+type Converged = Option<(usize,f64)>;
 
-	let pf = nas.p as f64;
+pub fn newton_fragment(c:&CF,s:&CU,nas:&NewtonApproxStatus,pr:PR,pal:Option<&PaletteStatus>) -> C {
+	precision_colors(
+		pr,
+		|| vec![newton_converge::<f64>(c,s,nas)],
+		|| vec![newton_converge::<f32>(c,s,nas)],
+		|a,b| newton_same(a,b,nas),
+		|r| newton_color(r,nas,pal)
+	).pop().unwrap()
+}
 
-	let p = rationalize_coord(c,&s,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
+/// 複数の点を LANES 個ずつまとめて計算する。結果は newton_fragment と一致する。
+pub fn newton_fragments(cs:&[CF],s:&CU,nas:&NewtonApproxStatus,pr:PR,pal:Option<&PaletteStatus>) -> Vec<C> {
+	precision_colors(
+		pr,
+		|| newton_converges::<f64>(cs,s,nas),
+		|| newton_converges::<f32>(cs,s,nas),
+		|a,b| newton_same(a,b,nas),
+		|r| newton_color(r,nas,pal)
+	)
+}
 
-	let mut z = Z { re:p.0, im:p.1 };
+fn newton_position<F:Float>(c:&CF,s:&CU) -> (F,F) {
+	let p = rationalize_coord(c,s,(1,1),RCOverflow::Keep,RCOrigin::Center).unwrap();
+	(F::from(p.0).unwrap(),F::from(p.1).unwrap())
+}
+
+/// 1点について収束したイテレート回数とその時点の偏角を返す。
+fn newton_converge<F:Float>(c:&CF,s:&CU,nas:&NewtonApproxStatus) -> Converged {
+
+	let pf  = F::from(nas.p).unwrap();
+	let tau = F::from(nas.tau).unwrap();
+	let eps = F::from(1e-6).unwrap();
+
+	let p = newton_position::<F>(c,s);
+	let mut z = Complex { re:p.0, im:p.1 };
 
 	for n in 0..nas.max {
-		let dz = - nas.tau * (z.powi(nas.p as i32)-1.0) / (pf*z.powi((nas.p-1) as i32));
-		if dz.abs()<=1e-6 {
-			let rgb = deg2rgb(z.arg()*RAD2DEG);
-			return match nas.speed {
-				false => C::Float{r:rgb.0*0.6,g:rgb.1*0.6,b:rgb.2*0.6,a:1.0},
+		let dz = (z.powi(nas.p as i32)-F::one()) * (-tau) / (z.powi((nas.p-1) as i32)*pf);
+		if dz.norm()<=eps {
+			return Some((n,z.arg().to_f64().unwrap()));
+		}
+		z = z + dz;
+	}
+
+	None
+
+}
+
+fn newton_converges<F:Float>(cs:&[CF],s:&CU,nas:&NewtonApproxStatus) -> Vec<Converged> {
+
+	cs.chunks(LANES).flat_map(|chunk| {
+
+		let mut z:LZ<F> = lanes_zero();
+		for (i,coord) in chunk.iter().enumerate() {
+			(z.0[i],z.1[i]) = newton_position(coord,s);
+		}
+
+		newton_lanes(z,chunk.len(),nas).into_iter().take(chunk.len())
+
+	}).collect()
+
+}
+
+/// 先頭の active 個のレーンについて、収束したイテレート回数とその時点の偏角を返す。
+/// 全てのレーンが収束した時点で打ち切る。
+fn newton_lanes<F:Float>(mut z:LZ<F>,active:usize,nas:&NewtonApproxStatus) -> [Converged;LANES] {
+
+	let pf  = F::from(nas.p).unwrap();
+	let tau = F::from(nas.tau).unwrap();
+	let eps = F::from(1e-6).unwrap();
+	let mut converged = [None;LANES];
+	let mut done = [false;LANES];
+	for d in done.iter_mut().skip(active) { *d = true; }
+
+	for n in 0..nas.max {
+
+		let zp = lanes_powi(&z,nas.p);
+		let zq = lanes_powi(&z,nas.p-1);
+
+		let mut num:LZ<F> = lanes_zero();
+		let mut den:LZ<F> = lanes_zero();
+		for i in 0..LANES {
+			num.0[i] = (zp.0[i]-F::one()) * (-tau);
+			num.1[i] = zp.1[i] * (-tau);
+			den.0[i] = zq.0[i]*pf;
+			den.1[i] = zq.1[i]*pf;
+		}
+		let dz = lanes_div(&num,&den);
+
+		let le = lanes_abs_le(&dz,eps);
+		for i in 0..LANES {
+			if !done[i] && le[i] {
+				done[i] = true;
+				converged[i] = Some((n,z.1[i].atan2(z.0[i]).to_f64().unwrap()));
+			}
+		}
+		if done.iter().all(|d| *d) { break; }
+
+		for i in 0..LANES {
+			z.0[i] = z.0[i] + dz.0[i];
+			z.1[i] = z.1[i] + dz.1[i];
+		}
+
+	}
+
+	converged
+
+}
+
+/// 精度による結果の違いを判定する。
+/// 収束先の根が異なるか、収束速度で色分けしている場合は収束までのイテレート回数が異なれば違うとみなす。
+fn newton_same(a:&Converged,b:&Converged,nas:&NewtonApproxStatus) -> bool {
+	let root = |arg:f64| ((arg/(2.0*PI)*(nas.p as f64)).round() as i64).rem_euclid(nas.p as i64);
+	match (a,b) {
+		(None,None) => true,
+		(Some((n1,a1)),Some((n2,a2))) => root(*a1)==root(*a2) && (!nas.speed || n1==n2),
+		_ => false
+	}
+}
+
+/// 収束先の偏角による色。収束しなかった点は黒。
+/// パレットが指定されていれば偏角を一周で 0 から 1 としたものをパレットの位置とする。
+fn newton_color(converged:Converged,nas:&NewtonApproxStatus,pal:Option<&PaletteStatus>) -> C {
+	match converged {
+		Some((n,arg)) => {
+			let (rgb,k) = match pal {
+				Some(pal) => (pal.color((arg/(2.0*PI)).rem_euclid(1.0)),1.0),
+				None => (deg2rgb(arg*RAD2DEG),0.6)
+			};
+			match nas.speed {
+				false => C::Float{r:rgb.0*k,g:rgb.1*k,b:rgb.2*k,a:1.0},
 				true  => {
 					let d = 1.0 - (n as f64) / (nas.max as f64);
 					C::Float{r:rgb.0*d,g:rgb.1*d,b:rgb.2*d,a:1.0}
 				}
-			};
+			}
+		},
+		None => C::GFloat{v:0.0,a:1.0}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	const SIZE:CU = (16,16);
+
+	/// 複素平面上の点 (re,im) に当たる座標
+	fn coord(re:f64,im:f64) -> CF {
+		((re+1.0)/2.0,(1.0-im)/2.0)
+	}
+
+	/// 16 × 16 の格子点と、根、原点、収束先の境目にある点
+	fn coords(p:usize) -> Vec<CF> {
+		let mut cs:Vec<CF> = (0..SIZE.0*SIZE.1).map(|i| {
+			(((i%SIZE.0) as f64+0.5)/(SIZE.0 as f64),((i/SIZE.0) as f64+0.5)/(SIZE.1 as f64))
+		}).collect();
+		let half = PI/(p as f64);
+		cs.extend([
+			coord(1.0,0.0),coord(0.0,0.0),coord(-1.0,0.0),
+			coord(0.5*half.cos(),0.5*half.sin()),coord(0.01*half.cos(),0.01*half.sin())
+		]);
+		cs
+	}
+
+	#[test]
+	fn simd_matches_scalar() {
+		for (p,max) in [(2,1000),(3,1000),(6,1000),(6,5)] {
+			let cs = coords(p);
+			for speed in [false,true] {
+				let nas = NewtonApproxStatus { p, tau:0.1, max, speed };
+				for pr in [PR::F64,PR::F32] {
+					let simd = newton_fragments(&cs,&SIZE,&nas,pr,None);
+					for (c,v) in cs.iter().zip(simd) {
+						assert!(newton_fragment(c,&SIZE,&nas,pr,None)==v,"{:?} p={} max={} speed={} {}",c,p,max,speed,pr.keyword());
+					}
+				}
+			}
 		}
-		z += dz;
 	}
 
-	C::GFloat{v:0.0,a:1.0}
+	#[test]
+	fn root_and_limit_points() {
+		let nas = NewtonApproxStatus { p:6, tau:0.1, max:5, speed:false };
+		assert_eq!(newton_converge::<f64>(&coord(1.0,0.0),&SIZE,&nas).map(|r| r.0),Some(0));
+		assert_eq!(newton_converge::<f64>(&coord(0.0,0.0),&SIZE,&nas),None);
+		assert_eq!(newton_converge::<f64>(&coord(0.5,0.5),&SIZE,&nas),None);
+	}
 
-}
\ No newline at end of file
+}
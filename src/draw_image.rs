@@ -1,68 +1,268 @@
-use image::{RgbaImage,Rgba,ImageEncoder};
+use image::{ImageBuffer,RgbaImage,Rgba,ColorType,ImageEncoder,ImageResult};
 use image::codecs::png::PngEncoder;
 use base64::encode as encode_base64;
-use rayon::prelude::*;
+use std::fs::write;
+use std::sync::atomic::Ordering;
 
 use crate::{
 	data::*,
-	draw_func::*
+	draw_func::*,
+	draw_func_colorbar::ycbcr_codes,
+	colorspace::srgb_decode,
+	scheme::Scheme,
+	draw_tile::par_tiles,
+	message::Msg
 };
 
 pub fn draw_image(s:Status) {
 
 	let output = s.output.as_ref().unwrap();
 
-	try_catch!( frame(&s,&s.size).save(output) );
+	try_catch!( save_image(&s,&s.size,output) );
 
 }
 
-fn frame(s:&Status,size:&CU) -> RgbaImage {
+/// 指定したサイズで描画した画像をファイルに保存する。
+/// 拡張子が .yuv なら Y'CbCr の生データ、 .exr なら線形な値の浮動小数点数の画像、そうでなければ s.depth のビット数の画像とする。
+/// HDR のカラーバーの PNG には色域と伝達関数を示す cICP チャンクを付ける。
+pub fn save_image(s:&Status,size:&CU,path:&str) -> ImageResult<()> {
+	let (w,h) = (size.0 as u32,size.1 as u32);
+	match (path.rsplit('.').next(),s.depth,cicp(s)) {
+		(Some("yuv"),_,_) => Ok(write(path,ycbcr_frame(s,size))?),
+		(Some("exr"),_,_) => linear_frame(s,size).save(path),
+		(Some("png"),16,Some(chunk)) => {
+			let data:Vec<u8> = frame16(s,size).into_raw().iter().flat_map(|v| v.to_ne_bytes()).collect();
+			save_png(path,&data,w,h,ColorType::Rgba16,chunk)
+		},
+		(Some("png"),_,Some(chunk)) => save_png(path,&frame(s,size).into_raw(),w,h,ColorType::Rgba8,chunk),
+		(_,16,_) => frame16(s,size).save(path),
+		_ => frame(s,size).save(path)
+	}
+}
 
-	let mut ib = RgbaImage::new(size.0 as u32,size.1 as u32);
+/// HDR のカラーバーの cICP (色域は BT.2020、伝達関数は PQ または HLG、行列なしの RGB、全範囲かどうか)
+fn cicp(s:&Status) -> Option<[u8;4]> {
+	match &s.draw_mode {
+		DM::Colorbar(ColorbarStatus{mode:CBSMode::HDR(tf),..}) => Some([
+			9,
+			match tf {
+				Transfer::Pq  => 16,
+				Transfer::Hlg => 18
+			},
+			0,
+			(s.levels==VL::Full) as u8
+		]),
+		_ => None
+	}
+}
+
+/// PNG の IHDR の直後に cICP チャンクを挿入して保存する。
+fn save_png(path:&str,data:&[u8],w:u32,h:u32,color:ColorType,cicp:[u8;4]) -> ImageResult<()> {
+	let mut png = Vec::new();
+	PngEncoder::new(&mut png).write_image(data,w,h,color)?;
+	// 8byteのシグネチャと IHDR (長さ、種類、13byteのデータ、 CRC) の後
+	let at = 8+4+4+13+4;
+	png.splice(at..at,png_chunk(b"cICP",&cicp));
+	Ok(write(path,png)?)
+}
+
+fn png_chunk(kind:&[u8;4],data:&[u8]) -> Vec<u8> {
+	let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+	chunk.extend(kind);
+	chunk.extend(data);
+	let crc = crc32(&chunk[4..]);
+	chunk.extend(crc.to_be_bytes());
+	chunk
+}
+
+/// PNG のチャンクの CRC-32 (多項式 0xedb88320)
+fn crc32(bytes:&[u8]) -> u32 {
+	!bytes.iter().fold(!0u32,|crc,b| {
+		(0..8).fold(crc^(*b as u32),|c,_| if c&1==1 { (c>>1)^0xedb88320 } else { c>>1 })
+	})
+}
+
+/// カラーバーの画素値の範囲。カラーバー以外は信号レベルを持たないため全範囲とする。
+fn video_levels(s:&Status) -> VL {
+	match s.draw_mode {
+		DM::Colorbar(_) => s.levels,
+		_ => VL::Full
+	}
+}
 
+/// ANSIカラー表示は端末の既定の背景色の上に描画する
+fn background(s:&Status) -> Option<(u8,u8,u8)> {
 	match s.draw_mode {
-		DM::Ansi => error!("ANSIカラー表示の出力には対応していません"),
-		_ => {}
+		DM::Ansi => Some(s.scheme.background),
+		_ => None
 	}
+}
+
+/// 指定したサイズで各ピクセルの色を計算し、 f で変換して並べる。
+fn pixels<T:Send,F:Fn(C) -> T + Sync>(s:&Status,size:&CU,f:F) -> Vec<T> {
+
+	let samples = aa_subpixels(s.aa, size, false, s.aa_pattern, s.aa_filter);
+	s.samples.store(0,Ordering::Relaxed);
 
-	let subpixels = aa_subpixels(s.aa, &s.size, false);
-
-	izip!(
-			iproduct!(0..size.1,0..size.0),
-			ib.pixels_mut()
-		).par_bridge()
-		.for_each(move |((y,x),p)| {
-
-			let c = get_color(x,y,&size,s,subpixels.as_slice());
-
-			*p = match c {
-				C::None|C::Reverse => Rgba([0,0,0,0]),
-				C::Float{r,g,b,a} => {
-					let (r,g,b,a) = (
-						(r*255.0).round() as u8,
-						(g*255.0).round() as u8,
-						(b*255.0).round() as u8,
-						(a*255.0).round() as u8
-					);
-					Rgba([r,g,b,a])
-				},
-				C::Int{r,g,b,a} => Rgba([r,g,b,a]),
-				C::GFloat{v,a} => {
-					let (v,a) = (
-						(v*255.0).round() as u8,
-						(a*255.0).round() as u8
-					);
-					Rgba([v,v,v,a])
-				},
-				_ => { panic!(); }
-			};
-
-		});
+	par_tiles(size.0,size.1,|coords| {
+		get_colors(coords,size,s,&samples).into_iter().map(&f).collect()
+	})
+
+}
+
+/// 指定したサイズで描画した画像を生成する。
+pub fn frame(s:&Status,size:&CU) -> RgbaImage {
+
+	let mut ib = RgbaImage::new(size.0 as u32,size.1 as u32);
+	let background = background(s);
+
+	let pixels = pixels(s,size,|c| {
+		let p = rgba(c,&s.scheme);
+		match background {
+			Some(bg) => over(p,bg),
+			None => p
+		}
+	});
+
+	for (p,c) in ib.pixels_mut().zip(pixels) { *p = c; }
 
 	ib
 
 }
 
+/// 1チャンネルあたり16bitの画像を生成する。
+fn frame16(s:&Status,size:&CU) -> ImageBuffer<Rgba<u16>,Vec<u16>> {
+
+	let mut ib = ImageBuffer::new(size.0 as u32,size.1 as u32);
+	let background = background(s);
+	// 狭い範囲は BT.2100 と同じく 8bit の符号値を 256 倍した値とする
+	let scale = match video_levels(s) {
+		VL::Full    => 65535.0,
+		VL::Limited => 65280.0
+	};
+	let q = |v:f64,scale:f64| (v*scale).round() as u16;
+
+	let pixels = pixels(s,size,|c| {
+		let (r,g,b,a) = straight_over(c.rgba(&s.scheme),background);
+		Rgba([q(r,scale),q(g,scale),q(b,scale),q(a,65535.0)])
+	});
+
+	for (p,c) in ib.pixels_mut().zip(pixels) { *p = c; }
+
+	ib
+
+}
+
+/// 10bit の Y'CbCr 4:4:4 の生データ (yuv444p10le) を生成する。
+/// 0 から 1 に切り詰める前の画素値を s.levels により信号レベルに戻すため、黒より下の信号や R'G'B' の範囲外の色も残る。
+/// 透明な部分は黒とし、係数はカラーバーでは各図の規格、それ以外では BT.709 とする。
+fn ycbcr_frame(s:&Status,size:&CU) -> Vec<u8> {
+
+	let background = background(s);
+	let luma = match &s.draw_mode {
+		DM::Colorbar(cbs) => cbs.mode.luma(),
+		_ => (0.2126,0.0722)
+	};
+
+	let levels = video_levels(s);
+
+	let codes = pixels(s,size,|c| {
+		let (r,g,b,a) = c.rgba(&s.scheme);
+		let (r,g,b,_) = match background {
+			Some(_) => straight_over((r,g,b,a),background),
+			None => (r*a,g*a,b*a,1.0)
+		};
+		let (y,cb,cr) = ycbcr_codes((levels.decode(r),levels.decode(g),levels.decode(b)),luma,10);
+		[y,cb,cr]
+	});
+
+	// Y', Cb, Cr の順に平面を並べる
+	let mut data = Vec::with_capacity(codes.len()*6);
+	for i in 0..3 {
+		for c in codes.iter() { data.extend(c[i].to_le_bytes()); }
+	}
+
+	data
+
+}
+
+/// 線形な光の量を浮動小数点数で持つ画像 (OpenEXR 用) を生成する。
+/// HDR のカラーバーは BT.2020 の表示される光 (1 を 100 cd/m² とする)、それ以外は sRGB の符号化を戻した値とする。
+fn linear_frame(s:&Status,size:&CU) -> ImageBuffer<Rgba<f32>,Vec<f32>> {
+
+	let mut ib = ImageBuffer::new(size.0 as u32,size.1 as u32);
+	let background = background(s);
+	let levels = video_levels(s);
+	let transfer = match &s.draw_mode {
+		DM::Colorbar(ColorbarStatus{mode:CBSMode::HDR(tf),..}) => Some(*tf),
+		_ => None
+	};
+
+	let pixels = pixels(s,size,|c| {
+		let (r,g,b,a) = straight_over(c.rgba(&s.scheme),background);
+		let (r,g,b) = match transfer {
+			Some(tf) => tf.display_light((levels.decode(r),levels.decode(g),levels.decode(b))),
+			None => (srgb_decode(r),srgb_decode(g),srgb_decode(b))
+		};
+		Rgba([r as f32,g as f32,b as f32,a as f32])
+	});
+
+	for (p,c) in ib.pixels_mut().zip(pixels) { *p = c; }
+
+	ib
+
+}
+
+/// 色を8bitのRGBAに変換する。 ANSI カラーと色名は配色 scheme の色とする。
+fn rgba(c:C,scheme:&Scheme) -> Rgba<u8> {
+	match c {
+		C::None => Rgba([0,0,0,0]),
+		C::Reverse => {
+			let (r,g,b) = scheme.foreground;
+			Rgba([r,g,b,255])
+		},
+		C::Float{r,g,b,a} => {
+			let (r,g,b,a) = (
+				(r*255.0).round() as u8,
+				(g*255.0).round() as u8,
+				(b*255.0).round() as u8,
+				(a*255.0).round() as u8
+			);
+			Rgba([r,g,b,a])
+		},
+		C::Int{r,g,b,a} => Rgba([r,g,b,a]),
+		C::GFloat{v,a} => {
+			let (v,a) = (
+				(v*255.0).round() as u8,
+				(a*255.0).round() as u8
+			);
+			Rgba([v,v,v,a])
+		},
+		c => {
+			let (r,g,b) = scheme.color(c.ansi_index().unwrap());
+			Rgba([r,g,b,255])
+		}
+	}
+}
+
+/// 半透明の色を不透明な背景色に重ねる。背景色がなければそのまま返す。
+fn straight_over((r,g,b,a):(f64,f64,f64,f64),bg:Option<(u8,u8,u8)>) -> (f64,f64,f64,f64) {
+	match bg {
+		Some((br,bg,bb)) => {
+			let mix = |v:f64,b:u8| v*a+(b as f64)/255.0*(1.0-a);
+			(mix(r,br),mix(g,bg),mix(b,bb),1.0)
+		},
+		None => (r,g,b,a)
+	}
+}
+
+/// 半透明の色を不透明な背景色に重ねる。
+fn over(p:Rgba<u8>,bg:(u8,u8,u8)) -> Rgba<u8> {
+	let a = (p.0[3] as f64)/255.0;
+	let mix = |v:u8,b:u8| ((v as f64)*a+(b as f64)*(1.0-a)).round() as u8;
+	Rgba([mix(p.0[0],bg.0),mix(p.0[1],bg.1),mix(p.0[2],bg.2),255])
+}
+
 pub fn base64_image(s:&Status) -> String {
 
 	let size = (s.size.0*4,s.size.1*4);
@@ -71,7 +271,7 @@ pub fn base64_image(s:&Status) -> String {
 	let encoder = PngEncoder::new(&mut png);
 
 	if let Err(e)=encoder.write_image(&frame(s,&size).into_raw(),size.0 as u32,size.1 as u32,image::ColorType::Rgba8) {
-		eprintln!("エラーが発生しました: {:?}",e);
+		eprintln!("{} {:?}",Msg::ErrorOccurred,e);
 		std::process::exit(1);
 	}
 
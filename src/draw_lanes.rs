@@ -0,0 +1,91 @@
+use std::array;
+use num::Float;
+
+/// 一度にまとめて計算する点の数
+pub const LANES:usize = 8;
+
+/// レーンごとの実数
+pub type L<F> = [F;LANES];
+
+/// レーンごとの複素数 (実部,虚部)
+pub type LZ<F> = (L<F>,L<F>);
+
+/// 全てのレーンが 0 の複素数
+pub fn lanes_zero<F:Float>() -> LZ<F> {
+	([F::zero();LANES],[F::zero();LANES])
+}
+
+/// 絶対値の比較で hypot を省略するための相対的な余裕
+/// 2乗和の丸め誤差は高々数 ulp なので、これより離れていれば hypot と同じ判定になる。
+fn hypot_margin<F:Float>() -> F {
+	F::epsilon()*F::from(64.0).unwrap()
+}
+
+/// 各レーンの複素数の積
+/// num::Complex の Mul と同じ順序で計算するため、結果はスカラーの計算と一致する。
+pub fn lanes_mul<F:Float>(a:&LZ<F>,b:&LZ<F>) -> LZ<F> {
+	let re = array::from_fn(|i| a.0[i]*b.0[i] - a.1[i]*b.1[i]);
+	let im = array::from_fn(|i| a.0[i]*b.1[i] + a.1[i]*b.0[i]);
+	(re,im)
+}
+
+/// 各レーンの複素数の商
+/// num::Complex の Div と同じ順序で計算する。
+pub fn lanes_div<F:Float>(a:&LZ<F>,b:&LZ<F>) -> LZ<F> {
+	let n:L<F> = array::from_fn(|i| b.0[i]*b.0[i] + b.1[i]*b.1[i]);
+	let re = array::from_fn(|i| (a.0[i]*b.0[i] + a.1[i]*b.1[i]) / n[i]);
+	let im = array::from_fn(|i| (a.1[i]*b.0[i] - a.0[i]*b.1[i]) / n[i]);
+	(re,im)
+}
+
+/// 各レーンの複素数の exp 乗
+/// num::Complex の powi と同じく二乗を繰り返す方法で、同じ順序で積を取る。
+pub fn lanes_powi<F:Float>(z:&LZ<F>,exp:usize) -> LZ<F> {
+
+	if exp==0 { return ([F::one();LANES],[F::zero();LANES]); }
+
+	let mut base = *z;
+	let mut exp = exp;
+	while exp&1==0 {
+		base = lanes_mul(&base,&base);
+		exp >>= 1;
+	}
+	if exp==1 { return base; }
+
+	let mut acc = base;
+	while exp>1 {
+		exp >>= 1;
+		base = lanes_mul(&base,&base);
+		if exp&1==1 {
+			acc = lanes_mul(&acc,&base);
+		}
+	}
+	acc
+
+}
+
+/// 各レーンで |z| > r かどうか
+/// 2乗和で判定できない境界付近のレーンだけ hypot で判定し直すため、 z.abs() > r と同じ結果になる。
+pub fn lanes_abs_gt<F:Float>(z:&LZ<F>,r:F) -> [bool;LANES] {
+	let r2 = r*r;
+	let (lo,hi) = (r2*(F::one()-hypot_margin()),r2*(F::one()+hypot_margin()));
+	array::from_fn(|i| {
+		let n = z.0[i]*z.0[i] + z.1[i]*z.1[i];
+		if n>hi { true }
+		else if n<lo { false }
+		else { z.0[i].hypot(z.1[i])>r }
+	})
+}
+
+/// 各レーンで |z| <= r かどうか
+/// lanes_abs_gt と同じく、 z.abs() <= r と同じ結果になる。
+pub fn lanes_abs_le<F:Float>(z:&LZ<F>,r:F) -> [bool;LANES] {
+	let r2 = r*r;
+	let (lo,hi) = (r2*(F::one()-hypot_margin()),r2*(F::one()+hypot_margin()));
+	array::from_fn(|i| {
+		let n = z.0[i]*z.0[i] + z.1[i]*z.1[i];
+		if n<lo { true }
+		else if n>hi { false }
+		else { z.0[i].hypot(z.1[i])<=r }
+	})
+}
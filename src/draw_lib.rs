@@ -1,11 +1,10 @@
 pub use crate::data::*;
+use crate::scheme::Scheme;
 use std::f64::consts::PI;
 
 extern crate num;
 pub use num::{Complex,complex::ComplexFloat};
 
-pub type Z = Complex<f64>;
-
 pub const RAD2DEG:f64 = 180.0/PI;
 
 pub fn rationalize_coord(coord:&CF,size:&CU,ratio:CU,overflow:RCOverflow,origin:RCOrigin) -> Option<CF> {
@@ -48,8 +47,8 @@ pub fn rationalize_coord(coord:&CF,size:&CU,ratio:CU,overflow:RCOverflow,origin:
 						norm.0.rem_euclid(2.0),
 						norm.1.rem_euclid(2.0)
 					);
-					if norm.0>1.0 { norm.0 = norm.0-2.0 }
-					if norm.1>1.0 { norm.1 = norm.1-2.0 }
+					if norm.0>1.0 { norm.0 -= 2.0 }
+					if norm.1>1.0 { norm.1 -= 2.0 }
 					Some(norm)
 				},
 				RCOverflow::Discard => {
@@ -72,6 +71,31 @@ pub enum RCOrigin {
 	TopLeft
 }
 
+/// --precision の指定に従い、 f64 と f32 の計算結果から色を決める。
+/// diff の場合は両方を計算し、 same で一致しないと判定された点を白で、一致する点を暗く描画する。
+pub fn precision_colors<T>(
+	pr:PR,
+	r64:impl FnOnce() -> Vec<T>,
+	r32:impl FnOnce() -> Vec<T>,
+	same:impl Fn(&T,&T) -> bool,
+	color:impl Fn(T) -> C
+) -> Vec<C> {
+	match pr {
+		PR::F64 => r64().into_iter().map(color).collect(),
+		PR::F32 => r32().into_iter().map(color).collect(),
+		PR::Diff => r64().into_iter().zip(r32()).map(|(a,b)| {
+			match same(&a,&b) {
+				false => C::GFloat{v:1.0,a:1.0},
+				true  => match color(a) {
+					C::Float{r,g,b,a} => C::Float{r:r*0.3,g:g*0.3,b:b*0.3,a},
+					C::GFloat{v,a}    => C::GFloat{v:v*0.3,a},
+					c => c
+				}
+			}
+		}).collect()
+	}
+}
+
 pub fn deg2rgb(deg:f64) -> (f64,f64,f64) {
 	let can = deg.rem_euclid(360.0);
 	let norm = can.rem_euclid(60.0)/60.0;
@@ -84,15 +108,57 @@ pub fn deg2rgb(deg:f64) -> (f64,f64,f64) {
 		},
 		match can {
 			t if ( t >    0.0 && t <   60.0 ) =>     norm,
-			t if ( t >=  60.0 && t <= 180.0 ) => 1.0     ,
+			t if (60.0..=180.0).contains(&t) => 1.0     ,
 			t if ( t >  180.0 && t <  240.0 ) => 1.0-norm,
 			_ => 0.0
 		},
 		match can {
 			t if ( t>  120.0 && t<  180.0 ) =>     norm,
-			t if ( t>= 180.0 && t<= 300.0 ) => 1.0     ,
+			t if (180.0..=300.0).contains(&t) => 1.0     ,
 			t if ( t>  300.0 && t<  360.0 ) => 1.0-norm,
 			_ => 0.0
 		}
 	)
-}
\ No newline at end of file
+}
+impl C {
+
+	/// 色名の ANSI カラーの番号 (KD が 0 の黒、 KL が 8 の明るい黒)
+	pub fn ansi_index(&self) -> Option<u8> {
+		match self {
+			C::Ansi(v) => Some(*v),
+			C::KD => Some(0), C::RD => Some(1), C::GD => Some(2), C::YD => Some(3),
+			C::BD => Some(4), C::MD => Some(5), C::CD => Some(6), C::WD => Some(7),
+			C::KL => Some(8), C::RL => Some(9), C::GL => Some(10),C::YL => Some(11),
+			C::BL => Some(12),C::ML => Some(13),C::CL => Some(14),C::WL => Some(15),
+			_ => None
+		}
+	}
+
+	/// 0 から 1 の RGBA の値
+	/// ANSI カラーと色名は配色 scheme の色とし、 C::Reverse は反転した既定の色として scheme の文字色とする。
+	/// C::None は端末の既定の背景色で描画されるため、透明とする。
+	pub fn rgba(&self,scheme:&Scheme) -> (f64,f64,f64,f64) {
+		let int = |v:u8| (v as f64)/255.0;
+		match self {
+			C::Float{r,g,b,a} => (*r,*g,*b,*a),
+			C::GFloat{v,a}    => (*v,*v,*v,*a),
+			C::Int{r,g,b,a}   => (int(*r),int(*g),int(*b),int(*a)),
+			C::None => (0.0,0.0,0.0,0.0),
+			C::Reverse => {
+				let (r,g,b) = scheme.foreground;
+				(int(r),int(g),int(b),1.0)
+			},
+			c => {
+				let (r,g,b) = scheme.color(c.ansi_index().unwrap());
+				(int(r),int(g),int(b),1.0)
+			}
+		}
+	}
+
+	/// 端末の色として出力される色かどうか。
+	/// サブピクセルが全て同じ端末の色なら、平均せずにその色のまま出力する。
+	pub fn is_terminal(&self) -> bool {
+		!matches!(self,C::Float{..}|C::GFloat{..}|C::None)
+	}
+
+}
@@ -3,20 +3,24 @@ use crossterm::{
 	QueueableCommand,
 	Result
 };
-use rayon::prelude::*;
 use std::io::{stdout,Write};
 
 use crossterm::tty::IsTty;
-use style::{Color,Stylize};
+use style::{Color,Stylize,StyledContent};
 use event::{Event,KeyCode,MouseEventKind};
 use std::env::var as getenv;
-use std::time::SystemTime as Time;
+use std::time::{SystemTime as Time,UNIX_EPOCH,Duration};
+use std::sync::atomic::Ordering;
 
 use crate::{
 	data::*,
 	draw_func::*,
+	draw_func_colorbar::{colorbar_level,ycbcr_codes},
+	draw_tile::par_tiles,
+	draw_aa::Samples,
 	draw_func_color::{color_mouse_down,color_mouse_drag},
-	draw_image::base64_image
+	draw_image::{base64_image,save_image},
+	message::Msg
 };
 
 pub fn draw_term(s:Status) {
@@ -26,19 +30,18 @@ pub fn draw_term(s:Status) {
 #[inline(always)]
 fn draw_term_impl(mut s:Status) -> Result<()> {
 
-	if !stdout().is_tty() { error!("出力結果をリダイレクトできません。"); }
+	if !stdout().is_tty() { error!(Msg::CannotRedirect); }
 
 	if matches!(s.draw_mode,DM::Ansi) {
-		if matches!(s.terminal,TM::Image) { error!("ANSIカラー表示の画像出力には対応していません"); }
-		s.color = TC::Full;
-		if matches!(s.pixels,TP::Double) { error!("ANSIカラー表示の double 出力には対応していません"); }
+		if matches!(s.terminal,TM::Image) { error!(Msg::AnsiTerminalImage); }
+		if matches!(s.pixels,TP::Double) { error!(Msg::AnsiDouble); }
 	}
-	else {
-		if matches!(s.color,TC::Auto) && matches!(s.terminal,TM::Texts) {
-			s.color = match getenv("COLORTERM") {
-				Ok(val) => if val.eq("truecolor") { TC::Full } else { TC::Less },
-				_ => TC::Less
-			}
+
+	// ANSIカラー表示から他の描画モードに切り替えた場合に備えて、常に色数を判定しておく
+	if matches!(s.color,TC::Auto) && matches!(s.terminal,TM::Texts) {
+		s.color = match getenv("COLORTERM") {
+			Ok(val) => if val.eq("truecolor") { TC::Full } else { TC::Less },
+			_ => TC::Less
 		}
 	}
 
@@ -53,31 +56,54 @@ fn draw_term_impl(mut s:Status) -> Result<()> {
 
 	s.size = terminal::size()?;
 	s.size.1 *= 2;
-	frame(&mut s)?;
+	// 描画が入力により中断され、描き直す必要があるかどうか
+	let mut incomplete = !redraw(&mut s)?;
 
 	loop {
+		if incomplete && !event::poll(Duration::ZERO)? {
+			incomplete = !redraw(&mut s)?;
+			continue;
+		}
 		match event::read()? {
 			Event::Key(e) => {
+				if s.hud.help {
+					s.hud.help = false;
+					incomplete = !clear_redraw(&mut s)?;
+					continue;
+				}
 				match e.code {
 					KeyCode::Char('q')|KeyCode::Enter|KeyCode::Esc => { break },
-					_ => {}
+					KeyCode::Char('s') => { screenshot(&s)?; },
+					KeyCode::Char('h') => {
+						s.hud.visible = !s.hud.visible;
+						incomplete = !clear_redraw(&mut s)?;
+					},
+					KeyCode::Char('?') => {
+						s.hud.help = true;
+						help_overlay(&s)?;
+					},
+					code => {
+						if edit_parameter(code,&mut s) { incomplete = !clear_redraw(&mut s)?; }
+					}
 				}
 			}
 			Event::Mouse(e) => {
+				let coord = mouse_coord(e.column,e.row,&s.size);
+				let mut redrawn = false;
+				if s.hud.visible {
+					s.hud.mouse = Some(fragment_position(&coord,&s.size,&s));
+				}
 				if draggable {
 					if let DM::Color(cs) = &mut s.draw_mode {
 						match e.kind {
 							MouseEventKind::Down(_) => {
-								let sp:CF = ( 0.5/(s.size.0 as f64) , 1.0/(s.size.1 as f64) );
-								let coord = unify_coord(e.column, e.row*2, &s.size, &[sp])[0];
 								color_mouse_down(&coord,&s.size,cs);
 							},
 							MouseEventKind::Drag(_)|MouseEventKind::Up(_) => {
 								let st = Time::now();
-								let sp:CF = ( 0.5/(s.size.0 as f64) , 1.0/(s.size.1 as f64) );
-								let coord = unify_coord(e.column, e.row*2, &s.size, &[sp])[0];
 								color_mouse_drag(&coord,&s.size,cs);
-								frame(&s)?;
+								incomplete = !redraw(&mut s)?;
+								redrawn = true;
 								let en = Time::now();
 								if let Ok(d)=en.duration_since(st) {
 									if d.as_secs_f64()>=0.1 { draggable = false; }
@@ -87,10 +113,11 @@ fn draw_term_impl(mut s:Status) -> Result<()> {
 						}
 					}
 				}
+				if s.hud.visible && !s.hud.help && !redrawn && !incomplete { hud_line(&s)?; }
 			}
 			Event::Resize(w,h) => {
 				s.size = (w,h*2);
-				frame(&mut s)?;
+				incomplete = !redraw(&mut s)?;
 			}
 		}
 	}
@@ -107,10 +134,361 @@ fn draw_term_impl(mut s:Status) -> Result<()> {
 	Ok(())
 }
 
-fn frame(s:&Status) -> Result<()> {
+/// キー入力に応じて描画モードのパラメータや出力の設定を変更する。変更があった場合は true を返す。
+fn edit_parameter(code:KeyCode,s:&mut Status) -> bool {
+
+	let is_ansi = matches!(s.draw_mode,DM::Ansi);
+	let aa_fixed = matches!(s.draw_mode,DM::Ansi|DM::Colorbar(_));
+	let paletted = matches!(s.draw_mode,DM::Mandelbrot(_)|DM::NewtonApprox(_));
+
+	match (code,&mut s.draw_mode) {
+		(KeyCode::Left ,DM::Color(cs)) => { cs.angle = (cs.angle-5.0).rem_euclid(360.0); },
+		(KeyCode::Right,DM::Color(cs)) => { cs.angle = (cs.angle+5.0).rem_euclid(360.0); },
+		(KeyCode::Char('w'),DM::Color(cs)) => {
+			cs.space = cs.space.next();
+			cs.chroma = cs.space.default_chroma();
+		},
+		(KeyCode::Char('l'),DM::Color(cs)) if cs.space!=CSP::Rgb => { cs.lightness = (cs.lightness+5.0).min(100.0); },
+		(KeyCode::Char('L'),DM::Color(cs)) if cs.space!=CSP::Rgb => { cs.lightness = (cs.lightness-5.0).max(0.0); },
+		(KeyCode::Char('k'),DM::Color(cs)) if cs.space!=CSP::Rgb => { cs.chroma += 5.0; },
+		(KeyCode::Char('K'),DM::Color(cs)) if cs.space!=CSP::Rgb => { cs.chroma = (cs.chroma-5.0).max(0.0); },
+		(KeyCode::Char('u'),DM::Cie(cs)) => { cs.uv = !cs.uv; },
+		(KeyCode::Char('w'),DM::Cie(cs)) => { cs.white = cs.white.next(); },
+		(KeyCode::Char('r'),DM::Colorbar(cbs)) => { cbs.repeat = !cbs.repeat; },
+		(KeyCode::Char('v'),DM::Colorbar(_)) => { s.levels = s.levels.next(); },
+		(KeyCode::Char('b'),DM::Colorbar(cbs)) if !matches!(cbs.mode,CBSMode::ARIB) => { cbs.mode = cbs.mode.next(); },
+		(KeyCode::Char('p'),DM::NewtonApprox(nas)) => { nas.p = (nas.p+1).min(64); },
+		(KeyCode::Char('P'),DM::NewtonApprox(nas)) => { nas.p = nas.p.saturating_sub(1).max(2); },
+		(KeyCode::Char('t'),DM::NewtonApprox(nas)) => { nas.tau = ((nas.tau*100.0+5.0).round()/100.0).min(2.0); },
+		(KeyCode::Char('T'),DM::NewtonApprox(nas)) => { nas.tau = ((nas.tau*100.0-5.0).round()/100.0).max(0.05); },
+		(KeyCode::Char('m'),DM::NewtonApprox(nas)) => { nas.max = nas.max.saturating_mul(2).min(1000000); },
+		(KeyCode::Char('M'),DM::NewtonApprox(nas)) => { nas.max = (nas.max/2).max(10); },
+		(KeyCode::Char('v'),DM::NewtonApprox(nas)) => { nas.speed = !nas.speed; },
+		(KeyCode::Char('+'|'='),DM::Mandelbrot(ms)) => { ms.zoom_by(2.0); },
+		(KeyCode::Char('-'),DM::Mandelbrot(ms)) => { ms.zoom_by(0.5); },
+		(KeyCode::Left ,DM::Mandelbrot(ms)) => { ms.pan(-0.25,0.0); },
+		(KeyCode::Right,DM::Mandelbrot(ms)) => { ms.pan( 0.25,0.0); },
+		(KeyCode::Up   ,DM::Mandelbrot(ms)) => { ms.pan(0.0, 0.25); },
+		(KeyCode::Down ,DM::Mandelbrot(ms)) => { ms.pan(0.0,-0.25); },
+		(KeyCode::Char('m'),DM::Mandelbrot(ms)) => { ms.set_max(ms.max.saturating_mul(2).min(1000000)); },
+		(KeyCode::Char('M'),DM::Mandelbrot(ms)) => { ms.set_max((ms.max/2).max(10)); },
+		(KeyCode::Char('d'),DM::Mandelbrot(ms)) => { ms.distance = !ms.distance; },
+		(KeyCode::Char(k @ ('o'|'O')),_) if paletted && s.palette.is_some() => {
+			let pal = s.palette.as_mut().unwrap();
+			let d = if k=='o' { 0.05 } else { -0.05 };
+			pal.offset = ((pal.offset+d).rem_euclid(1.0)*100.0).round()/100.0;
+		},
+		(KeyCode::Char('a'),_) if !aa_fixed => {
+			s.aa = match s.aa {
+				0|1 => 2,
+				2 => 3,
+				3 => 4,
+				_ => 0
+			};
+		},
+		(KeyCode::Char('x'),_) if !is_ansi => {
+			s.pixels = match s.pixels {
+				TP::Single => TP::Double,
+				TP::Double => TP::Single
+			};
+		},
+		(KeyCode::Char('c'),_) if !is_ansi && matches!(s.terminal,TM::Texts) => {
+			s.color = match s.color {
+				TC::Full => TC::Less,
+				_ => TC::Full
+			};
+		},
+		(KeyCode::Char(n @ '0'..='9'),_) => {
+			let i = ((n as usize)+9-('0' as usize))%10;
+			let target = ModeName::ALL[i];
+			return switch_mode(s,target);
+		},
+		(KeyCode::Tab,_) => {
+			// 数字キーのない描画モードも含めて順に切り替える
+			let n = ModeName::ALL.len();
+			let i = ModeName::ALL.iter().position(|m| *m==s.draw_mode.name()).unwrap_or(0);
+			return (1..n).any(|k| switch_mode(s,ModeName::ALL[(i+k)%n]));
+		},
+		_ => { return false; }
+	}
+
+	true
+
+}
+
+/// 描画モードを切り替える。切り替え前の描画モードの状態は other_modes に保存しておく。
+fn switch_mode(s:&mut Status,target:ModeName) -> bool {
+
+	if s.draw_mode.name()==target { return false; }
+	if target==ModeName::Ansi && matches!(s.terminal,TM::Image) { return false; }
+
+	let i = match s.other_modes.iter().position(|(dm,_)| dm.name()==target) {
+		Some(i) => i,
+		None => { return false; }
+	};
+	let (dm,aa) = s.other_modes.swap_remove(i);
+	let prev = std::mem::replace(&mut s.draw_mode,dm);
+	s.other_modes.push((prev,s.aa));
+	s.aa = aa;
+	s.hud.mouse = None;
+
+	true
+
+}
+
+/// マウスの位置をセルの中心の正規化された座標に変換する。
+fn mouse_coord(column:u16,row:u16,size:&CU) -> CF {
+	let sp:CF = ( 0.5/(size.0 as f64) , 1.0/(size.1 as f64) );
+	unify_coord(column, row*2, size, &[sp])[0]
+}
+
+/// フレームを描画して描画時間を記録し、オーバーレイを重ねる。
+/// 入力により描画が中断された場合は false を返す。
+fn redraw(s:&mut Status) -> Result<bool> {
+
+	let st = Time::now();
+	let completed = frame(s)?;
+	let elapsed = Time::now().duration_since(st).map(|d| d.as_secs_f64()).unwrap_or(0.0);
+
+	// 中断された場合は次回も段階的に描画するよう、描画時間を短く見積もらない
+	s.hud.frame_time = if completed { elapsed } else { s.hud.frame_time.max(elapsed) };
+
+	if !completed { return Ok(false); }
+
+	if s.hud.visible { hud_line(s)?; }
+	if s.hud.help { help_overlay(s)?; }
+
+	Ok(true)
+
+}
+
+/// オーバーレイを消すために画面を消去してから描画し直す。
+fn clear_redraw(s:&mut Status) -> Result<bool> {
+	stdout()
+		.queue(terminal::Clear(terminal::ClearType::All))?
+		.flush()?;
+	redraw(s)
+}
+
+/// 画面の最下行に描画モード、パラメータ、マウス位置、描画時間、色数を表示する。
+fn hud_line(s:&Status) -> Result<()> {
+
+	let name = s.draw_mode.name().keyword();
+	let precision = match s.precision {
+		PR::F64 => String::new(),
+		pr => format!(" {}",pr.keyword())
+	};
+	let palette = match &s.palette {
+		Some(pal) if pal.offset!=0.0 => format!(" {}{:+.2}",pal.gradient.name,pal.offset),
+		Some(pal) => format!(" {}",pal.gradient.name),
+		None => String::new()
+	};
+	let mode = match &s.draw_mode {
+		DM::Color(cs) if cs.space==CSP::Rgb => format!("{} {:.1}°",name,cs.angle),
+		DM::Color(cs) => format!(
+			"{} {:.1}° {} L={} C={}",
+			name,cs.angle,cs.space.keyword(),cs.lightness,cs.chroma
+		),
+		DM::Ansi => format!("{} {}",name,s.scheme.name),
+		DM::Colorbar(cbs) => format!(
+			"{}{}{}{}",
+			name,
+			match cbs.mode {
+				CBSMode::RP219(patch) => format!(" rp219 {}",patch.keyword()),
+				CBSMode::EBU{full,split} => format!(" {}{}",if full { "100" } else { "75" },if split { " split" } else { "" }),
+				CBSMode::HDR(tf) => format!(" {}",tf.keyword()),
+				_ => String::new()
+			},
+			if cbs.repeat { " repeat" } else { "" },
+			if s.levels==VL::Limited { " limited" } else { "" }
+		),
+		DM::Cie(cs) => format!("{} {} {}",name,if cs.uv { "u'v'" } else { "xy" },cs.white.keyword()),
+		DM::Mandelbrot(ms) => {
+			// 拡大率に応じて、画素の間隔が分かる桁数まで中心を表示する
+			let digits = (ms.zoom.log10().max(0.0) as usize)+4;
+			let im = ms.im.to_decimal(digits);
+			format!(
+				"{} {}{}{}i ×{:.1e} max={}{}{}{}{}",
+				name,ms.re.to_decimal(digits),if im.starts_with('-') { "" } else { "+" },im,ms.zoom,ms.max,
+				if ms.distance { " distance" } else { "" },
+				if ms.deep() { " deep" } else { "" },
+				precision,palette
+			)
+		},
+		DM::NewtonApprox(nas) => format!(
+			"{} p={} τ={} max={}{}{}{}",
+			name,nas.p,nas.tau,nas.max,
+			if nas.speed { " speed" } else { "" },
+			precision,palette
+		)
+	};
+
+	// カラーバーではマウス位置のパッチの Y'CbCr の符号値を表示する
+	let codes = match (&s.draw_mode,s.hud.mouse) {
+		(DM::Colorbar(cbs),Some(p)) => colorbar_level(&p,&s.size,cbs,s.levels).map(|l| (ycbcr_codes(l,cbs.mode.luma(),10),ycbcr_codes(l,cbs.mode.luma(),8))),
+		_ => None
+	};
+	let mouse = match (codes,s.hud.mouse) {
+		(Some((c10,c8)),_) => format!("Y'CbCr 10bit {}/{}/{} 8bit {}/{}/{}",c10.0,c10.1,c10.2,c8.0,c8.1,c8.2),
+		(None,Some((x,y))) => format!("({:.4},{:.4})",x,y),
+		(None,None) => String::from("-")
+	};
+
+	let depth = match (s.terminal,effective_color(s)) {
+		(TM::Image,_)    => "image",
+		(_,TC::Full)     => "24bit",
+		(_,TC::Less)     => "256",
+		(_,TC::Auto)     => "auto"
+	};
+
+	let aa = match (s.aa_pattern,s.aa_filter) {
+		(AP::Grid,AF::Box) => format!("{}",s.aa),
+		(p,f) => format!("{} {} {}",s.aa,p.keyword(),f.keyword())
+	};
+
+	let aa = match s.aa_threshold {
+		Some(t) => format!("{} adaptive={} {} samples",aa,t,s.samples.load(Ordering::Relaxed)),
+		None => aa
+	};
+
+	let text = format!(
+		" {} | aa={} | {} | {:.1}ms | {} ",
+		mode,aa,mouse,s.hud.frame_time*1000.0,depth
+	);
+
+	status_line(s,&text)
+
+}
+
+/// ヘルプに表示するキー操作の一覧
+const KEY_BINDINGS:&[(&str,Msg)] = &[
+	("q Enter Esc",Msg::KeyQuit),
+	("s"          ,Msg::KeyScreenshot),
+	("h"          ,Msg::KeyHud),
+	("?"          ,Msg::KeyHelp),
+	("1-9 0 Tab"  ,Msg::KeyMode),
+	("drag ← →"   ,Msg::KeyDrag),
+	("w"          ,Msg::KeySpace),
+	("l L k K"    ,Msg::KeyLightness),
+	("u"          ,Msg::KeyUv),
+	("w"          ,Msg::KeyWhite),
+	("r"          ,Msg::KeyRepeat),
+	("b"          ,Msg::KeyBars),
+	("v"          ,Msg::KeyLevels),
+	("p P"        ,Msg::KeyNewtonP),
+	("t T"        ,Msg::KeyNewtonTau),
+	("m M"        ,Msg::KeyNewtonMax),
+	("+ -"        ,Msg::KeyZoom),
+	("↑ ↓ ← →"    ,Msg::KeyPan),
+	("d"          ,Msg::KeyDistance),
+	("o O"        ,Msg::KeyPalette),
+	("v"          ,Msg::KeyNewtonSpeed),
+	("a"          ,Msg::KeyAA),
+	("x"          ,Msg::KeyPixels),
+	("c"          ,Msg::KeyColor)
+];
+
+/// 画面の中央にキー操作の一覧を表示する。
+fn help_overlay(s:&Status) -> Result<()> {
+
+	let mut lines = vec![format!("{}",Msg::HelpTitle),String::new()];
+	for (key,msg) in KEY_BINDINGS.iter() {
+		lines.push(format!("{:<12} {}",key,msg));
+	}
+	lines.push(String::new());
+	lines.push(format!("{}",Msg::HelpClose));
+
+	let width = lines.iter().map(|l| text_width(l)).max().unwrap_or(0)+4;
+	let top  = (s.size.1/2).saturating_sub(lines.len() as u16)/2;
+	let left = s.size.0.saturating_sub(width as u16)/2;
+
+	for (i,l) in lines.iter().enumerate() {
+		let padded = format!("  {}{}  ",l," ".repeat(width-4-text_width(l)));
+		stdout()
+			.queue(cursor::MoveTo(left,top+i as u16))?
+			.queue(style::PrintStyledContent(padded.reverse()))?;
+	}
+
+	stdout()
+		.queue(cursor::MoveTo(0,0))?
+		.flush()?;
+
+	Ok(())
+
+}
+
+/// 端末上での表示幅を概算する。CJK の全角文字を幅2として数える。
+pub fn text_width(text:&str) -> usize {
+	text.chars().map(|c| match c as u32 {
+		0x1100..=0x115F|0x2E80..=0xA4CF|0xAC00..=0xD7A3|0xF900..=0xFAFF|0xFE30..=0xFE4F|0xFF00..=0xFF60|0xFFE0..=0xFFE6 => 2,
+		_ => 1
+	}).sum()
+}
+
+/// 現在の表示内容を shot_scale 倍の解像度で描画し、カレントディレクトリに PNG として保存する。
+fn screenshot(s:&Status) -> Result<()> {
+
+	// 倍率は設定で自由に指定できるため、 u16 に収まらない大きさは保存しない
+	let size = match (s.size.0.checked_mul(s.shot_scale),s.size.1.checked_mul(s.shot_scale)) {
+		(Some(w),Some(h)) => (w,h),
+		_ => { return status_line(s,&format!("{} {}",Msg::ScreenshotFailed,Msg::ScreenshotTooLarge)); }
+	};
+	let path = format!("visualizer-{}.png",timestamp());
+	let msg = match save_image(s,&size,&path) {
+		Ok(_)  => format!("{} {}",Msg::ScreenshotSaved,path),
+		Err(e) => format!("{} {:?}",Msg::ScreenshotFailed,e)
+	};
+
+	status_line(s,&msg)
+
+}
+
+/// 画面の最下行にメッセージを表示する。次のフレームの描画で上書きされる。
+fn status_line(s:&Status,msg:&str) -> Result<()> {
+
+	let mut text = String::new();
+	for c in msg.chars() {
+		if text_width(&text)+text_width(&c.to_string())>(s.size.0 as usize) { break; }
+		text.push(c);
+	}
+
+	stdout()
+		.queue(cursor::MoveTo(0,(s.size.1/2).saturating_sub(1)))?
+		.queue(terminal::Clear(terminal::ClearType::CurrentLine))?
+		.queue(style::PrintStyledContent(text.reverse()))?
+		.queue(cursor::MoveTo(0,0))?
+		.flush()?;
+
+	Ok(())
+
+}
+
+/// UTC の現在時刻を YYYYmmdd-HHMMSS の形式で返す。
+fn timestamp() -> String {
+
+	let secs = Time::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+	let (days,rem) = ((secs/86400) as i64,secs%86400);
+
+	// 1970-01-01 からの日数をグレゴリオ暦の日付に変換する
+	let z = days+719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe-doe/1460+doe/36524-doe/146096)/365;
+	let doy = doe-(365*yoe+yoe/4-yoe/100);
+	let mp = (5*doy+2)/153;
+	let d = doy-(153*mp+2)/5+1;
+	let m = if mp<10 { mp+3 } else { mp-9 };
+	let y = yoe+era*400+if m<=2 { 1 } else { 0 };
+
+	format!("{:04}{:02}{:02}-{:02}{:02}{:02}",y,m,d,rem/3600,rem%3600/60,rem%60)
+
+}
+
+/// フレームを描画する。入力により描画が中断された場合は false を返す。
+fn frame(s:&Status) -> Result<bool> {
 	match s.terminal {
 		TM::Texts => frame_texts(s),
-		TM::Image => frame_image(s)
+		TM::Image => frame_image(s).map(|_| true)
 	}
 }
 
@@ -118,90 +496,154 @@ const SPACE:&str = " ";
 const UPPER:&str = "▀";
 const LOWER:&str = "▄";
 
+/// 1タイルあたりのテキストの行数
+const TILE_ROWS:u16 = 8;
+
+/// 前回のフレームの描画にこれ以上の秒数を要した場合は、粗い描画から段階的に描画する
+const PROGRESSIVE_THRESHOLD:f64 = 0.05;
+
+/// フレームを描画する。描画中に入力があった場合は中断して false を返す。
+/// 描画に時間のかかる場合は、粗いプレビュー、アンチエイリアスなし、アンチエイリアスありの順に描画を重ねる。
 #[inline(always)]
-fn frame_texts(s:&Status) -> Result<()> {
+fn frame_texts(s:&Status) -> Result<bool> {
 
 	stdout()
 		.queue(terminal::DisableLineWrap)?;
 
-	let subpixels = aa_subpixels(s.aa, &s.size, match s.pixels {
+	let stretched = match effective_pixels(s) {
 		TP::Single => true,
 		TP::Double => false
-	});
+	};
 
-	let cells:Vec<_> =
-		iproduct!(
-			(0..s.size.1).step_by(2),
-			0..s.size.0
-		).par_bridge()
-		.map(move |(y,x)| {
+	// (ブロックの大きさ,サブピクセル)
+	let mut passes:Vec<(u16,Samples)> = vec![];
+	if s.hud.frame_time>=PROGRESSIVE_THRESHOLD {
+		passes.push((4,aa_subpixels(0,&s.size,stretched,AP::Grid,AF::Box)));
+		if s.aa>0 { passes.push((1,aa_subpixels(0,&s.size,stretched,AP::Grid,AF::Box))); }
+	}
+	passes.push((1,aa_subpixels(s.aa,&s.size,stretched,s.aa_pattern,s.aa_filter)));
+	s.samples.store(0,Ordering::Relaxed);
 
-			let osc = match s.pixels {
+	for (block,samples) in passes.iter() {
+		if !frame_texts_pass(s,*block,samples)? { return Ok(false); }
+	}
 
-				TP::Single => {
+	stdout()
+		.queue(cursor::MoveTo(0,0))?
+		.flush()?;
 
-					let c = get_color(x,y,&s.size,s,subpixels.as_slice());
+	Ok(true)
+}
 
-					match c {
-						C::None    => None,
-						C::Reverse => Some(SPACE.reverse()),
-						_ => {
-							let color = convert_color(c,s);
-							Some(SPACE.on(color))
-						}
-					}
+/// block × block のセルごとに1回だけ色を計算して画面全体を描画する。
+/// タイルを描画するごとに入力を確認し、入力があれば中断して false を返す。
+fn frame_texts_pass(s:&Status,block:u16,samples:&Samples) -> Result<bool> {
 
-				},
-
-				TP::Double => {
-
-					let c1 = get_color(x,y  ,&s.size,s,subpixels.as_slice());
-					let c2 = get_color(x,y+1,&s.size,s,subpixels.as_slice());
-
-					match (c1,c2) {
-						(C::None,C::None) => None,
-						(c1,C::None) => {
-							let color = convert_color(c1,s);
-							Some(UPPER.with(color))
-						},
-						(C::None,c2) => {
-							let color = convert_color(c2,s);
-							Some(LOWER.with(color))
-						},
-						(c1,c2) => {
-							let color1 = convert_color(c1,s);
-							let color2 = convert_color(c2,s);
-							Some(LOWER.on(color1).with(color2))
-						}
-					}
+	let rows = s.size.1/2;
+	let tile = TILE_ROWS*block;
 
-				}
+	for r0 in (0..rows).step_by(tile as usize) {
 
-			};
+		let r1 = (r0+tile).min(rows);
+
+		// ブロック単位の格子上でタイルに分けて計算する
+		let (gw,gh) = (s.size.0.div_ceil(block),(r1-r0).div_ceil(block));
+		let cells = par_tiles(gw,gh,|coords| {
+			let cells:Vec<CU> = coords.iter().map(|&(i,j)| (i*block,r0+j*block)).collect();
+			cells.iter().zip(cells_content(s,&cells,samples)).map(|(&(x,r),sc)| (x,r,sc)).collect()
+		});
+
+		for (x,r,osc) in cells.into_iter() {
+			let sc = osc.unwrap_or_else(|| style::style(SPACE));
+			for (dy,dx) in iproduct!(0..block,0..block) {
+				if r+dy>=r1 || x+dx>=s.size.0 { continue; }
+				stdout()
+					.queue(cursor::MoveTo(x+dx,r+dy))?
+					.queue(style::PrintStyledContent(sc))?;
+			}
+		}
+
+		stdout().flush()?;
+
+		if event::poll(Duration::ZERO)? { return Ok(false); }
+
+	}
+
+	Ok(true)
+}
+
+/// 画面上の複数のセルの表示内容をまとめて計算する。セルの座標の縦方向は行単位。
+fn cells_content(s:&Status,cells:&[CU],samples:&Samples) -> Vec<Option<StyledContent<&'static str>>> {
+
+	match effective_pixels(s) {
+
+		TP::Single => {
+
+			let pixels:Vec<CU> = cells.iter().map(|&(x,r)| (x,r*2)).collect();
 
-			(x,y,osc)
+			get_colors(&pixels,&s.size,s,samples).into_iter().map(|c| {
+				match c {
+					C::None    => None,
+					C::Reverse => Some(SPACE.reverse()),
+					_ => {
+						let color = convert_color(c,s);
+						Some(SPACE.on(color))
+					}
+				}
+			}).collect()
+
+		},
 
-		})
-		.collect();
+		TP::Double => {
+
+			let pixels:Vec<CU> = cells.iter().flat_map(|&(x,r)| [(x,r*2),(x,r*2+1)]).collect();
+			let mut cs = get_colors(&pixels,&s.size,s,samples).into_iter();
+
+			cells.iter().map(|_| {
+				let (c1,c2) = (cs.next().unwrap(),cs.next().unwrap());
+				match (c1,c2) {
+					(C::None,C::None) => None,
+					(c1,C::None) => {
+						let color = convert_color(c1,s);
+						Some(UPPER.with(color))
+					},
+					(C::None,c2) => {
+						let color = convert_color(c2,s);
+						Some(LOWER.with(color))
+					},
+					(c1,c2) => {
+						let color1 = convert_color(c1,s);
+						let color2 = convert_color(c2,s);
+						Some(LOWER.on(color1).with(color2))
+					}
+				}
+			}).collect()
 
-	for (x,y,osc) in cells.into_iter() {
-		if let Some(sc) = osc {
-			stdout()
-				.queue(cursor::MoveTo(x,y/2))?
-				.queue(style::PrintStyledContent(sc))?;
 		}
+
 	}
 
-	stdout()
-		.queue(cursor::MoveTo(0,0))?
-		.flush()?;
+}
 
-	Ok(())
+/// ANSIカラー表示は常にフルカラーで描画する。
+fn effective_color(s:&Status) -> TC {
+	match s.draw_mode {
+		DM::Ansi => TC::Full,
+		_ => s.color
+	}
+}
+
+/// ANSIカラー表示は常に single で描画する。
+fn effective_pixels(s:&Status) -> TP {
+	match s.draw_mode {
+		DM::Ansi => TP::Single,
+		_ => s.pixels
+	}
 }
 
 fn convert_color(c:C,s:&Status) -> Color {
 	match c {
-		C::Float{r,g,b,a:_} => match s.color {
+		C::Float{r,g,b,a:_} => match effective_color(s) {
 			TC::Full => Color::Rgb{
 				r:(r*255.0).round() as u8,
 				g:(g*255.0).round() as u8,
@@ -215,7 +657,7 @@ fn convert_color(c:C,s:&Status) -> Color {
 			},
 			_ => { panic!(); }
 		},
-		C::Int{r,g,b,a:_} => match s.color {
+		C::Int{r,g,b,a:_} => match effective_color(s) {
 			TC::Full => Color::Rgb{r,g,b},
 			TC::Less => {
 				let ri = ((r as f64)/256.0*5.0) as u8;
@@ -225,7 +667,7 @@ fn convert_color(c:C,s:&Status) -> Color {
 			},
 			_ => { panic!(); }
 		},
-		C::GFloat{v,a:_} => match s.color {
+		C::GFloat{v,a:_} => match effective_color(s) {
 			TC::Full => {
 				let u = (v*255.0).round() as u8;
 				Color::Rgb{r:u,g:u,b:u}
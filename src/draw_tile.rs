@@ -0,0 +1,43 @@
+use rayon::prelude::*;
+use crate::data::CU;
+
+/// タイルの一辺の大きさ
+pub const TILE_SIZE:u16 = 16;
+
+/// w × h の格子を TILE_SIZE 四方のタイルに分割し、タイル単位でスレッドに分配して f を評価する。
+/// f にはタイル内の座標を行優先で並べたものが渡され、同じ数の結果を同じ順で返す。
+/// タイル内はまとめて評価するため、スレッドの割り当ての単位が大きく、結果の並びも常に同じになる。
+/// 結果は格子の行優先の順に並ぶ。
+pub fn par_tiles<T,F>(w:u16,h:u16,f:F) -> Vec<T>
+where
+	T: Send,
+	F: Fn(&[CU]) -> Vec<T> + Sync
+{
+
+	let tiles:Vec<(u16,u16)> = iproduct!(
+		(0..h).step_by(TILE_SIZE as usize),
+		(0..w).step_by(TILE_SIZE as usize)
+	).collect();
+
+	let results:Vec<Vec<T>> = tiles
+		.par_iter()
+		.map(|&(y0,x0)| {
+			let x1 = x0.saturating_add(TILE_SIZE).min(w);
+			let y1 = y0.saturating_add(TILE_SIZE).min(h);
+			let coords:Vec<CU> = iproduct!(y0..y1,x0..x1).map(|(y,x)| (x,y)).collect();
+			f(&coords)
+		})
+		.collect();
+
+	let mut grid:Vec<Option<T>> = (0..(w as usize)*(h as usize)).map(|_| None).collect();
+	for (&(y0,x0),values) in tiles.iter().zip(results) {
+		let tw = (x0.saturating_add(TILE_SIZE).min(w)-x0) as usize;
+		for (i,v) in values.into_iter().enumerate() {
+			let (x,y) = (x0 as usize+i%tw,y0 as usize+i/tw);
+			grid[y*(w as usize)+x] = Some(v);
+		}
+	}
+
+	grid.into_iter().map(|v| v.unwrap()).collect()
+
+}
@@ -1,7 +1,7 @@
 #[macro_export]
 macro_rules! error {
 	($msg:expr) => {{
-		eprintln!($msg);
+		eprintln!("{}",$msg);
 		std::process::exit(1);
 	}};
 }
@@ -10,8 +10,15 @@ macro_rules! error {
 macro_rules! try_catch {
 	($trial:expr) => {
 		if let Err(e) = $trial {
-			eprintln!("エラーが発生しました:\n{:?}",e);
+			eprintln!("{}\n{:?}",$crate::message::Msg::ErrorOccurred,e);
 			std::process::exit(1);
 		}
 	};
-}
\ No newline at end of file
+}
+/// "#rrggbb" または "rrggbb" の形式の16進数の色
+pub fn parse_hex(text:&str) -> Option<(u8,u8,u8)> {
+	let h = text.strip_prefix('#').unwrap_or(text);
+	if h.len()!=6 || !h.is_ascii() { return None; }
+	let v = |i:usize| u8::from_str_radix(&h[i..i+2],16).ok();
+	Some((v(0)?,v(2)?,v(4)?))
+}
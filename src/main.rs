@@ -10,27 +10,52 @@ extern crate rayon;
 mod library;
 mod data;
 mod args;
+mod config;
+mod bench;
+mod message;
 mod draw_term;
 mod draw_image;
 mod draw_lib;
 mod draw_func;
+mod draw_tile;
+mod draw_aa;
+mod draw_lanes;
+mod bigfixed;
+mod palette;
+mod scheme;
+mod query;
+mod colorspace;
 mod draw_func_color;
 mod draw_func_colorbar;
+mod draw_func_cie;
 mod draw_func_mandelbrot;
 mod draw_func_newton;
 mod draw_func_ansi;
 
-use clap::Parser;
+use rayon::ThreadPoolBuilder;
 use crate::{
-	args::init_status,
-	data::Args,
+	args::{parse_args,init_status},
+	data::EDM,
+	bench::bench,
+	query::query_colors,
 	draw_term::draw_term,
 	draw_image::draw_image
 };
 
 fn main() {
-	let args = Args::parse();
+	let args = parse_args();
+	if let Some(EDM::Bench {kind}) = &args.draw_mode {
+		bench(&args,kind);
+		return;
+	}
+	if let Some(EDM::Colors {json,timeout}) = &args.draw_mode {
+		query_colors(*json,*timeout);
+		return;
+	}
 	let status = init_status(&args);
+	if status.threads>0 {
+		try_catch!( ThreadPoolBuilder::new().num_threads(status.threads).build_global() );
+	}
 	match status.output {
 		None    => draw_term(status),
 		Some(_) => draw_image(status)
@@ -0,0 +1,343 @@
+use std::fmt;
+use std::env::{args as getargs,var as getenv};
+use std::sync::OnceLock;
+use clap::{ArgEnum,Command};
+
+use crate::data::*;
+
+static LANGUAGE:OnceLock<LG> = OnceLock::new();
+
+/// メッセージの表示に使用する言語を返す。
+/// --lang の指定があればそれを優先し、なければ LC_ALL, LC_MESSAGES, LANG の順に環境変数を参照する。
+pub fn language() -> LG {
+	*LANGUAGE.get_or_init(detect_language)
+}
+
+fn detect_language() -> LG {
+
+	// clap による解析の前にヘルプの言語を決める必要があるため、 --lang は自前で読み取る
+	let argv:Vec<String> = getargs().collect();
+	for (i,a) in argv.iter().enumerate() {
+		let val = match a.strip_prefix("--lang=") {
+			Some(v) => Some(v),
+			None if a=="--lang" => argv.get(i+1).map(|v| v.as_str()),
+			None => None
+		};
+		if let Some(Ok(l)) = val.map(|v| LG::from_str(v,true)) { return l; }
+	}
+
+	for key in ["LC_ALL","LC_MESSAGES","LANG"] {
+		match getenv(key) {
+			Ok(v) if v.is_empty() => continue,
+			Ok(v) if v.starts_with("ja") => return LG::Ja,
+			Ok(v) if v=="C" || v=="POSIX" => return LG::Ja,
+			Ok(_) => return LG::En,
+			Err(_) => continue
+		}
+	}
+
+	LG::Ja
+
+}
+
+/// 実行時に表示するメッセージ
+pub enum Msg {
+	ErrorOccurred,
+	ImageSizeMissing,
+	ImageWidthMissing,
+	ImageHeightMissing,
+	ImageSizeInvalid,
+	ImageWidthInvalid,
+	ImageHeightInvalid,
+	CannotRedirect,
+	AnsiTerminalImage,
+	AnsiDouble,
+	ConfigInvalid,
+	ConfigUnreadable,
+	ConfigUnknownKey,
+	ConfigModeIgnored,
+	PresetNotFound,
+	CenterInvalid,
+	DegreeInvalid,
+	PaletteInvalid,
+	SchemeInvalid,
+	DepthInvalid,
+	QueryNoResponse,
+	QueryIndex,
+	QueryActual,
+	QueryDiffer,
+	QueryMissing,
+	ScreenshotSaved,
+	ScreenshotFailed,
+	ScreenshotTooLarge,
+	HelpTitle,
+	HelpClose,
+	KeyQuit,
+	KeyScreenshot,
+	KeyHud,
+	KeyHelp,
+	KeyMode,
+	KeyDrag,
+	KeyRepeat,
+	KeyBars,
+	KeyLevels,
+	KeyNewtonP,
+	KeyNewtonTau,
+	KeyNewtonMax,
+	KeyNewtonSpeed,
+	KeyZoom,
+	KeyPan,
+	KeyDistance,
+	KeyUv,
+	KeyWhite,
+	KeySpace,
+	KeyLightness,
+	KeyPalette,
+	KeyAA,
+	KeyPixels,
+	KeyColor
+}
+
+impl Msg {
+	/// (日本語,英語) の組
+	fn texts(&self) -> (&'static str,&'static str) {
+		match self {
+			Msg::ErrorOccurred      => ("エラーが発生しました:","An error has occurred:"),
+			Msg::ImageSizeMissing   => ("画像サイズが指定されていません","Image size is not specified"),
+			Msg::ImageWidthMissing  => ("画像の横幅が指定されていません","Image width is not specified"),
+			Msg::ImageHeightMissing => ("画像の縦幅が指定されていません","Image height is not specified"),
+			Msg::ImageSizeInvalid   => ("画像サイズが不正です","Invalid image size"),
+			Msg::ImageWidthInvalid  => ("画像の横幅が不正です","Invalid image width"),
+			Msg::ImageHeightInvalid => ("画像の縦幅が不正です","Invalid image height"),
+			Msg::CannotRedirect     => ("出力結果をリダイレクトできません。","Output cannot be redirected."),
+			Msg::AnsiTerminalImage  => ("ANSIカラー表示の画像出力には対応していません","The ANSI color test cannot be displayed as an image"),
+			Msg::AnsiDouble         => ("ANSIカラー表示の double 出力には対応していません","The ANSI color test does not support double pixels"),
+			Msg::ConfigInvalid      => ("設定ファイルを読み込めません:","Failed to read the config file:"),
+			Msg::ConfigUnreadable   => ("設定ファイルを開けないため無視します:","Ignoring the config file that cannot be opened:"),
+			Msg::ConfigUnknownKey   => ("設定ファイルの不明な項目を無視します:","Ignoring an unknown key in the config file:"),
+			Msg::ConfigModeIgnored  => ("設定ファイルの値が不正なため、この描画モードでは既定値を使います:","Using the defaults for this draw mode because the config file has an invalid value:"),
+			Msg::PresetNotFound     => ("指定されたプリセットが見つかりません:","Preset not found:"),
+			Msg::CenterInvalid      => ("中心座標が不正です:","Invalid center:"),
+			Msg::DegreeInvalid      => ("次数 p は 2 以上を指定してください:","The degree p must be at least 2:"),
+			Msg::PaletteInvalid     => ("パレットを読み込めません:","Failed to load the palette:"),
+			Msg::SchemeInvalid      => ("配色を読み込めません:","Failed to load the color scheme:"),
+			Msg::DepthInvalid       => ("ビット数は 8 または 16 を指定してください","The bit depth must be 8 or 16"),
+			Msg::QueryNoResponse    => ("端末が色の問い合わせに応答しませんでした","The terminal did not answer the color queries"),
+			Msg::QueryIndex         => ("番号","index"),
+			Msg::QueryActual        => ("端末","terminal"),
+			Msg::QueryDiffer        => ("xterm の既定値と異なる色 (*) の数:","Colors differing from the xterm defaults (*):"),
+			Msg::QueryMissing       => ("応答のなかったパレットの色の数:","Palette entries without an answer:"),
+			Msg::ScreenshotSaved    => ("スクリーンショットを保存しました:","Saved screenshot:"),
+			Msg::ScreenshotFailed   => ("スクリーンショットを保存できません:","Failed to save screenshot:"),
+			Msg::ScreenshotTooLarge => ("画像サイズが大きすぎます (--shot-scale を小さくしてください)","Image too large (lower --shot-scale)"),
+			Msg::HelpTitle          => ("キー操作","Key bindings"),
+			Msg::HelpClose          => ("いずれかのキーで閉じます","Press any key to close"),
+			Msg::KeyQuit            => ("終了","Quit"),
+			Msg::KeyScreenshot      => ("スクリーンショットを保存","Save a screenshot"),
+			Msg::KeyHud             => ("ステータスバーの表示切替","Toggle the status bar"),
+			Msg::KeyHelp            => ("このヘルプを表示","Show this help"),
+			Msg::KeyMode            => ("描画モードを切替 (ansi hue conic bv bvw smpte arib mandelbrot newton cie ebu hdr)","Switch mode (ansi hue conic bv bvw smpte arib mandelbrot newton cie ebu hdr)"),
+			Msg::KeyDrag            => ("色相をずらす (色相環・色相図)","Shift the hue (hue wheels and charts)"),
+			Msg::KeyRepeat          => ("図の繰り返しを切替 (カラーバー)","Toggle repeat (color bars)"),
+			Msg::KeyBars            => ("カラーバーの種類を切替 (smpte ebu hdr)","Switch the kind of color bars (smpte, ebu, hdr)"),
+			Msg::KeyLevels          => ("信号レベルの範囲を切替 full limited (カラーバー)","Switch levels full limited (color bars)"),
+			Msg::KeyNewtonP         => ("次数 p を増減 (ニュートン近似)","Increase/decrease p (newton)"),
+			Msg::KeyNewtonTau       => ("収束因子 τ を増減 (ニュートン近似)","Increase/decrease τ (newton)"),
+			Msg::KeyNewtonMax       => ("最大イテレート回数を倍/半分 (マンデルブロ集合・ニュートン近似)","Double/halve max iterations (mandelbrot, newton)"),
+			Msg::KeyNewtonSpeed     => ("収束速度による色分けを切替 (ニュートン近似)","Toggle speed coloring (newton)"),
+			Msg::KeyZoom            => ("拡大/縮小 (マンデルブロ集合)","Zoom in/out (mandelbrot)"),
+			Msg::KeyPan             => ("表示範囲を移動 (マンデルブロ集合)","Move the view (mandelbrot)"),
+			Msg::KeyDistance        => ("距離推定による濃淡を切替 (マンデルブロ集合)","Toggle distance estimation shading (mandelbrot)"),
+			Msg::KeySpace           => ("色空間を切替 rgb oklch lch hsluv (色相環・色相図)","Switch color space rgb oklch lch hsluv (hue wheels and charts)"),
+			Msg::KeyLightness       => ("明度・彩度を増減 (rgb 以外の色相環・色相図)","Increase/decrease lightness and chroma (non-rgb hue wheels and charts)"),
+			Msg::KeyUv              => ("xy と u'v' を切替 (色度図)","Toggle xy / u'v' (chromaticity diagram)"),
+			Msg::KeyWhite           => ("白色点を切替 (色度図)","Switch the white point (chromaticity diagram)"),
+			Msg::KeyPalette         => ("パレットの周期をずらす (--palette 指定時)","Shift the palette cycle (with --palette)"),
+			Msg::KeyAA              => ("アンチエイリアスのレベルを切替","Cycle antialiasing level"),
+			Msg::KeyPixels          => ("single/double 解像度を切替","Toggle single/double pixels"),
+			Msg::KeyColor           => ("less/full 色数を切替","Toggle less/full colors")
+		}
+	}
+}
+
+impl fmt::Display for Msg {
+	fn fmt(&self,f:&mut fmt::Formatter) -> fmt::Result {
+		let (ja,en) = self.texts();
+		f.write_str(match language() {
+			LG::Ja => ja,
+			LG::En => en
+		})
+	}
+}
+
+/// 英語のヘルプ (サブコマンド名,引数名,説明)
+/// 引数名が空の場合はサブコマンド自体の説明を表す。日本語のヘルプは data.rs のドキュメントコメントがそのまま使われる。
+const HELP_EN:&[(&str,&str,&str)] = &[
+	("","","Draws various pictures on the terminal. The subcommand selects what to draw; ansi is selected when omitted. See visualizer help <SUBCOMMAND> for the options of each subcommand. Full color is used automatically when the terminal is detected to support it."),
+	("","output","Path of the image file to write. Draws to the terminal when omitted."),
+	("","width","Width of the image file. Ignored in terminal mode."),
+	("","height","Height of the image file. Ignored in terminal mode."),
+	("","terminal","Output format (texts/image) when drawing to the terminal. Images are supported only by some terminals. Defaults to texts."),
+	("","color","Number of colors used for text output. auto uses full color if the terminal supports it. less and full force the setting regardless of the terminal. Defaults to auto."),
+	("","pixels","Resolution of text output. Some terminals cannot display double resolution properly. Defaults to single."),
+	("","aa","Antialiasing level. An integer of 2 or more enables antialiasing. Pixels whose subpixels all share the same terminal color (such as an ANSI color) keep that color without averaging. Defaults to 2, or 0 for ansi and the color bars so that their edges stay sharp."),
+	("","aa-pattern","Placement of antialiasing samples. grid is a regular grid, rotated a grid tilted so that each row and column holds one sample, jitter a grid with each sample moved randomly inside its cell using a fixed seed, halton and sobol low-discrepancy sequences, and poisson a Poisson-disk set. Defaults to grid."),
+	("","aa-filter","Reconstruction filter weighting antialiasing samples. box averages the samples inside the pixel; tent, gaussian and lanczos also weight samples beyond the pixel boundary. Wider filters take more samples. Defaults to box."),
+	("","adaptive-aa","Enables adaptive antialiasing. Each pixel center is computed once and only pixels whose color differs from the neighbors above, below, left or right by more than the threshold are supersampled."),
+	("","no-adaptive-aa","Disables adaptive antialiasing enabled in the config file or a preset."),
+	("","aa-threshold","Color difference threshold for adaptive antialiasing, as a 0 to 1 difference of any RGBA component. Defaults to 0.02."),
+	("","linear-aa","Whether antialiasing averages subpixels in linear light by undoing the sRGB encoding, as true or false. false reproduces the former rendering that averages the encoded values, which darkens edges. Defaults to true."),
+	("","preset","Applies a preset defined in the config file ($XDG_CONFIG_HOME/visualizer/config.toml). Command line options take priority over the preset."),
+	("","shot-scale","Scale of the screenshot saved with the s key in terminal mode, as image pixels per screen pixel. Defaults to 8."),
+	("","hud","Shows the draw mode, parameters, mouse position, render time and color depth on the bottom line in terminal mode. Toggle it with the h key."),
+	("","no-hud","Does not show the HUD at startup even if the config file or a preset enables it."),
+	("","threads","Number of threads used for rendering. Defaults to the number of logical CPU cores."),
+	("","kernel","How the Mandelbrot set and Newton's method are computed. simd computes several points at once. Both give the same result. Defaults to simd."),
+	("","palette","Palette used for the Mandelbrot set and Newton's method. Specify a built-in palette (viridis, magma, inferno, cividis, twilight) or the path of a GIMP gradient (.ggr) or a file listing hex colors. Hue is used when omitted."),
+	("","palette-offset","Shift of the palette cycle as a fraction from 0 to 1. Defaults to 0."),
+	("","palette-repeat","Number of times the palette is repeated. Defaults to 1."),
+	("","ansi-scheme","Terminal color scheme used for named ANSI colors, for example when writing the ANSI test to an image. Specify a built-in scheme (xterm, vga, solarized, tango) or the path of an iTerm2 color preset (.itermcolors) or a Windows Terminal scheme in JSON. Defaults to xterm."),
+	("","levels","Range of pixel values for the signal levels of the color bars. full maps black to 0 and white to 255. limited maps black to 16 and white to 235 and keeps the PLUGE steps below black and signals above white in the pixel values. Defaults to full."),
+	("","depth","Bits per channel of PNG output, 8 or 16. 16 keeps level differences that 8 bits round off. If the output path ends in .yuv, raw 10-bit Y'CbCr 4:4:4 (yuv444p10le) is written instead, and .exr writes linear floating-point OpenEXR. PNG output of hdr carries a cICP chunk. Defaults to 16 for hdr and 8 otherwise."),
+	("","precision","Floating-point precision used for the Mandelbrot set and Newton's method. diff computes both f64 and f32 and draws the points where they disagree in white and the others darkened. Defaults to f64."),
+	("","lang","Language of messages and help. Defaults to LC_ALL, LC_MESSAGES or LANG."),
+	("ansi","","Tests the ANSI color display of the terminal."),
+	("hue","","Draws a hue wheel with a white center."),
+	("hue","rotate","Rotation of the hue wheel in degrees. Defaults to 0."),
+	("hue","space","Color space. Other than rgb, hues are drawn at fixed lightness and chroma and colors outside sRGB are striped. Defaults to rgb."),
+	("hue","lightness","Lightness in percent from 0 to 100 for color spaces other than rgb. Defaults to 70."),
+	("hue","chroma","Chroma in percent for color spaces other than rgb. 100% is 0.4 in OKLCH, 150 in CIELCh and the largest chroma inside sRGB in HSLuv. Defaults to 100 for hsluv and 30 otherwise."),
+	("conic","","Draws a hue wheel."),
+	("conic","rotate","Rotation of the hue wheel in degrees. Defaults to 0."),
+	("conic","space","Color space. Other than rgb, hues are drawn at fixed lightness and chroma and colors outside sRGB are striped. Defaults to rgb."),
+	("conic","lightness","Lightness in percent from 0 to 100 for color spaces other than rgb. Defaults to 70."),
+	("conic","chroma","Chroma in percent for color spaces other than rgb. 100% is 0.4 in OKLCH, 150 in CIELCh and the largest chroma inside sRGB in HSLuv. Defaults to 100 for hsluv and 30 otherwise."),
+	("bvw","","Draws hue horizontally and HSL lightness vertically."),
+	("bvw","offset","Horizontal shift of the picture as a hue angle in degrees. Defaults to 0."),
+	("bvw","space","Color space. Other than rgb, hues are drawn at fixed lightness and chroma and colors outside sRGB are striped. Defaults to rgb."),
+	("bvw","lightness","Lightness in percent from 0 to 100 for color spaces other than rgb. Defaults to 70."),
+	("bvw","chroma","Chroma in percent for color spaces other than rgb. 100% is 0.4 in OKLCH, 150 in CIELCh and the largest chroma inside sRGB in HSLuv. Defaults to 100 for hsluv and 30 otherwise."),
+	("bv","","Draws hue horizontally and HSV value vertically."),
+	("bv","offset","Horizontal shift of the picture as a hue angle in degrees. Defaults to 0."),
+	("bv","space","Color space. Other than rgb, hues are drawn at fixed lightness and chroma and colors outside sRGB are striped. Defaults to rgb."),
+	("bv","lightness","Lightness in percent from 0 to 100 for color spaces other than rgb. Defaults to 70."),
+	("bv","chroma","Chroma in percent for color spaces other than rgb. 100% is 0.4 in OKLCH, 150 in CIELCh and the largest chroma inside sRGB in HSLuv. Defaults to 100 for hsluv and 30 otherwise."),
+	("smpte","","Draws the SMPTE color bars for analog broadcasting. With --hd, draws the SMPTE RP 219 HD color bars."),
+	("smpte","repeat","Repeats the picture."),
+	("smpte","no-repeat","Overrides repeat in the config file or a preset and does not repeat the picture."),
+	("smpte","hd","Draws the 16:9 SMPTE RP 219 HD color bars with +I, -I and PLUGE."),
+	("smpte","no-hd","Overrides hd in the config file or a preset and draws the color bars for analog broadcasting."),
+	("smpte","patch","Patch second from the left of Pattern 2 with --hd. Defaults to plus-i."),
+	("mandelbrot","","Draws the Mandelbrot set."),
+	("mandelbrot","re","Real part of the center of the view, in decimal. More digits than f64 can hold may be given. Defaults to -0.5."),
+	("mandelbrot","im","Imaginary part of the center of the view, in decimal. More digits than f64 can hold may be given. Defaults to 0."),
+	("mandelbrot","zoom","Magnification. From 1e12 on, the picture is rendered as perturbations from a reference orbit computed in arbitrary precision. Defaults to 1."),
+	("mandelbrot","max","Maximum number of iterations. Points that do not diverge within it are drawn black. Defaults to 200."),
+	("mandelbrot","distance","Shades by the estimated distance to the boundary instead of the iteration count. Points closer to the boundary are darker."),
+	("mandelbrot","no-distance","Overrides distance in the config file or a preset and shades by the iteration count."),
+	("newton","","Draws the argument of the root that Newton's method converges to for the p-th roots of unity, as hue."),
+	("newton","p","Degree p of the roots. Defaults to 6."),
+	("newton","tau","Convergence factor τ. Defaults to 0.1."),
+	("newton","max","Maximum number of iterations. Points that do not converge within it are drawn black. Defaults to 1000."),
+	("newton","speed","Colors by the time taken to converge. Points that take longer are darker."),
+	("newton","no-speed","Overrides speed in the config file or a preset and colors by the argument of the root."),
+	("arib","","Draws the ARIB color bars for digital broadcasting."),
+	("arib","repeat","Repeats the picture."),
+	("arib","no-repeat","Overrides repeat in the config file or a preset and does not repeat the picture."),
+	("ebu","","Draws the EBU color bars (100/0/75/0)."),
+	("ebu","repeat","Repeats the picture."),
+	("ebu","no-repeat","Overrides repeat in the config file or a preset and does not repeat the picture."),
+	("ebu","full","Draws the 100/0/100/0 color bars."),
+	("ebu","no-full","Overrides full in the config file or a preset and draws the 100/0/75/0 color bars."),
+	("ebu","split","Draws the BBC split-field color bars, with the lower half in greys of the same luma as each bar."),
+	("ebu","no-split","Overrides split in the config file or a preset."),
+	("hdr","","Draws the ITU-R BT.2111 HDR color bars. --levels selects narrow (limited) or full range."),
+	("hdr","repeat","Repeats the picture."),
+	("hdr","no-repeat","Overrides repeat in the config file or a preset and does not repeat the picture."),
+	("hdr","transfer","Transfer function, pq (SMPTE ST 2084) or hlg. Defaults to pq."),
+	("cie","","Draws the CIE 1931 xy chromaticity diagram filled with the nearest displayable colors, with the sRGB (solid), Display P3 (dashed), Rec.2020 (dotted) and Adobe RGB (dash-dot) gamut triangles and a white point mark."),
+	("cie","uv","Draws the CIE 1976 u'v' chromaticity diagram instead."),
+	("cie","no-uv","Overrides uv in the config file or a preset and draws the CIE 1931 xy chromaticity diagram."),
+	("cie","white","White point to mark. dci is the DCI-P3 white point. Defaults to d65."),
+	("colors","","Queries the actual colors of the terminal with OSC 4 (palette 0-255), OSC 10 (foreground) and OSC 11 (background) and shows them with the standard xterm values. Differing colors are marked with *. Only differing colors of 16-255 are listed."),
+	("colors","json","Prints the result as JSON. It uses the names of a Windows Terminal scheme, so it can be given to --ansi-scheme as is when the foreground, background and colors 0-15 were all answered. Colors without an answer are left out."),
+	("colors","timeout","Time in milliseconds to wait for the answers. Defaults to 1000."),
+	("bench","","Measures rendering performance."),
+	("tiles","","Renders the same picture as image output with varying thread counts and measures the parallel scaling. The image size and antialiasing follow --width, --height and --aa."),
+	("tiles","mode","Draw mode to measure."),
+	("tiles","repeat","Number of trials for each thread count. The shortest time is used."),
+	("cpu","","Renders a fixed workload (Mandelbrot set and Newton's method) with varying thread counts and shows the time, speedup, parallel efficiency and a score, followed by comparisons at the maximum thread count of the scalar and simd kernels, f64 and f32 precision, and full and adaptive antialiasing. Terminal output is not measured. The maximum thread count follows --threads."),
+	("cpu","repeat","Number of trials for each thread count. The shortest time is used."),
+	("help","","Shows how to use the visualizer command.")
+];
+
+/// clap が生成する --help の説明 (日本語,英語)
+const HELP_FLAG:(&str,&str) = ("visualizer コマンドの使い方を表示します。","Shows how to use the visualizer command.");
+
+/// 言語設定に従ってコマンドのヘルプを置き換える。
+pub fn localize_command(cmd:Command) -> Command {
+	match language() {
+		LG::Ja => cmd.mut_arg("help",|arg| arg.help(HELP_FLAG.0)),
+		LG::En => localize_command_with(cmd,"",HELP_EN).mut_arg("help",|arg| arg.help(HELP_FLAG.1))
+	}
+}
+
+fn localize_command_with<'h>(mut cmd:Command<'h>,name:&str,table:&'static [(&'static str,&'static str,&'static str)]) -> Command<'h> {
+
+	for &(_,a,text) in table.iter().filter(|(c,_,_)| *c==name) {
+		cmd = match a {
+			"" => cmd.about(text).long_about(None),
+			_  => cmd.mut_arg(a,|arg| arg.help(text).long_help(None))
+		};
+	}
+
+	for sc in cmd.get_subcommands_mut() {
+		let sub_name = String::from(sc.get_name());
+		let sub = std::mem::take(sc);
+		*sc = localize_command_with(sub,&sub_name,table);
+	}
+
+	cmd
+
+}
+
+#[cfg(test)]
+mod tests {
+
+	use clap::{Command,CommandFactory};
+	use crate::data::Args;
+	use super::HELP_EN;
+
+	/// 英語のヘルプがないサブコマンドと引数を name から再帰的に集める。
+	fn missing(cmd:&Command,name:&str,out:&mut Vec<String>) {
+		let has = |a:&str| HELP_EN.iter().any(|(c,n,_)| *c==name && *n==a);
+		if !has("") { out.push(format!("{} (about)",name)); }
+		for arg in cmd.get_arguments().filter(|a| !["help","version"].contains(&a.get_id())) {
+			if !has(arg.get_id()) { out.push(format!("{} {}",name,arg.get_id())); }
+		}
+		for sc in cmd.get_subcommands() {
+			missing(sc,sc.get_name(),out);
+		}
+	}
+
+	#[test]
+	fn every_argument_has_english_help() {
+		let mut out = vec![];
+		missing(&Args::command(),"",&mut out);
+		assert!(out.is_empty(),"missing English help: {:?}",out);
+	}
+
+	#[test]
+	fn no_stale_english_help() {
+		let cmd = Args::command();
+		for (c,a,_) in HELP_EN {
+			let sc = match *c {
+				"" => Some(&cmd),
+				_ => cmd.get_subcommands().chain(cmd.get_subcommands().flat_map(|s| s.get_subcommands())).find(|s| s.get_name()==*c)
+			};
+			let sc = sc.unwrap_or_else(|| panic!("unknown subcommand {}",c));
+			assert!(a.is_empty() || sc.get_arguments().any(|arg| arg.get_id()==*a),"unknown argument {} {}",c,a);
+		}
+	}
+
+}
@@ -0,0 +1,275 @@
+use std::f64::consts::PI;
+use std::fs::read_to_string;
+
+use crate::data::*;
+use crate::library::parse_hex;
+
+/// 組み込みのパレット
+/// matplotlib の同名のカラーマップを等間隔に取った色。 twilight は両端が同じ色になる周期的なパレットで、値は近似。
+const BUILTIN:&[(&str,&[&str])] = &[
+	("viridis",&["440154","482475","414487","355f8d","2a788e","21918c","22a884","44bf70","7ad151","bddf26","fde725"]),
+	("magma"  ,&["000004","140e36","3b0f70","641a80","8c2981","b73779","de4968","f7705c","fe9f6d","fecf92","fcfdbf"]),
+	("inferno",&["000004","160b39","420a68","6a176e","932667","bc3754","dd513a","f37819","fca50a","f6d746","fcffa4"]),
+	("cividis",&["00204d","00336f","39486b","575c6d","707173","8a8779","a69d75","c4b56c","e4cf5b","ffea46"]),
+	("twilight",&["e2d9e2","9ab9c8","6181bc","5a3fa0","2f1436","7a2249","b45a4e","d4a294","e2d9e2"])
+];
+
+/// 区間内の補間の方法 (GIMP のグラデーションの blend type)
+#[derive(Clone,Copy)]
+enum Blend {
+	Linear,
+	Curved,
+	Sine,
+	SphereIncreasing,
+	SphereDecreasing,
+	Step
+}
+
+/// グラデーションの1区間
+/// left から right までを left_color から right_color へ補間する。 middle は2色の中間の色になる位置。
+struct Segment {
+	left:   f64,
+	middle: f64,
+	right:  f64,
+	left_color:  (f64,f64,f64),
+	right_color: (f64,f64,f64),
+	blend:  Blend
+}
+
+/// 0 から 1 の値を色に対応させるグラデーション
+pub struct Gradient {
+	pub name: String,
+	segments: Vec<Segment>
+}
+
+impl Gradient {
+
+	/// 組み込みのパレット名、または GIMP のグラデーション (.ggr) か16進数の色を並べたファイルのパスから読み込む。
+	pub fn load(spec:&str) -> Result<Gradient,String> {
+		if let Some((name,colors)) = BUILTIN.iter().find(|(n,_)| *n==spec) {
+			let colors = colors.iter().map(|c| parse_hex_f64(c).unwrap()).collect();
+			return Ok(Gradient::from_colors(name,colors));
+		}
+		let text = read_to_string(spec).map_err(|e| format!("{}: {}",spec,e))?;
+		match text.starts_with("GIMP Gradient") {
+			true  => parse_ggr(spec,&text),
+			false => parse_hex_list(spec,&text)
+		}
+	}
+
+	/// 色を等間隔に並べて線形に補間するグラデーション
+	fn from_colors(name:&str,colors:Vec<(f64,f64,f64)>) -> Gradient {
+		let n = (colors.len()-1).max(1) as f64;
+		let segments = match colors.len() {
+			1 => vec![linear_segment(0.0,1.0,colors[0],colors[0])],
+			_ => colors.windows(2).enumerate()
+				.map(|(i,w)| linear_segment((i as f64)/n,((i+1) as f64)/n,w[0],w[1]))
+				.collect()
+		};
+		Gradient { name:String::from(name), segments }
+	}
+
+	/// t (0 から 1) の位置の色
+	pub fn at(&self,t:f64) -> (f64,f64,f64) {
+
+		let t = t.clamp(0.0,1.0);
+		let seg = self.segments.iter()
+			.find(|s| t<=s.right)
+			.unwrap_or(self.segments.last().unwrap());
+
+		let len = seg.right-seg.left;
+		let pos = if len>0.0 { (t-seg.left)/len } else { 0.5 };
+		let mid = if len>0.0 { (seg.middle-seg.left)/len } else { 0.5 };
+
+		let f = match seg.blend {
+			Blend::Linear => linear_factor(pos,mid),
+			Blend::Curved => pos.powf(0.5f64.ln()/mid.max(1e-10).ln()),
+			Blend::Sine   => ((-PI/2.0+PI*linear_factor(pos,mid)).sin()+1.0)/2.0,
+			Blend::SphereIncreasing => {
+				let l = linear_factor(pos,mid)-1.0;
+				(1.0-l*l).max(0.0).sqrt()
+			},
+			Blend::SphereDecreasing => {
+				let l = linear_factor(pos,mid);
+				1.0-(1.0-l*l).max(0.0).sqrt()
+			},
+			Blend::Step => if pos>=mid { 1.0 } else { 0.0 }
+		};
+
+		let (a,b) = (seg.left_color,seg.right_color);
+		(a.0+(b.0-a.0)*f,a.1+(b.1-a.1)*f,a.2+(b.2-a.2)*f)
+
+	}
+
+}
+
+impl PaletteStatus {
+	/// 値 t に周期のずらし量と繰り返し回数を適用した色
+	/// t は 0 から 1 の範囲とし、繰り返しやずらしにより 1 を超えた分は先頭に戻る。
+	pub fn color(&self,t:f64) -> (f64,f64,f64) {
+		let u = t*self.repeat+self.offset;
+		let u = if u!=0.0 && u.rem_euclid(1.0)==0.0 { 1.0 } else { u.rem_euclid(1.0) };
+		self.gradient.at(u)
+	}
+}
+
+/// 区間の中間点の位置を考慮した線形の補間の割合
+fn linear_factor(pos:f64,mid:f64) -> f64 {
+	if pos<=mid {
+		if mid<=0.0 { 0.0 } else { 0.5*pos/mid }
+	}
+	else {
+		if mid>=1.0 { 1.0 } else { 0.5+0.5*(pos-mid)/(1.0-mid) }
+	}
+}
+
+fn linear_segment(left:f64,right:f64,a:(f64,f64,f64),b:(f64,f64,f64)) -> Segment {
+	Segment {
+		left,
+		middle: (left+right)/2.0,
+		right,
+		left_color: a,
+		right_color: b,
+		blend: Blend::Linear
+	}
+}
+
+/// 16進数の色を 0 から 1 の実数にする。
+fn parse_hex_f64(text:&str) -> Option<(f64,f64,f64)> {
+	let (r,g,b) = parse_hex(text)?;
+	Some(((r as f64)/255.0,(g as f64)/255.0,(b as f64)/255.0))
+}
+
+/// 空白、カンマ、改行で区切った16進数の色の並び
+fn parse_hex_list(name:&str,text:&str) -> Result<Gradient,String> {
+	let colors = text
+		.split(|c:char| c.is_whitespace() || c==',')
+		.filter(|t| !t.is_empty())
+		.map(|t| parse_hex_f64(t).ok_or(format!("{}: {}",name,t)))
+		.collect::<Result<Vec<_>,_>>()?;
+	match colors.is_empty() {
+		true  => Err(String::from(name)),
+		false => Ok(Gradient::from_colors(name,colors))
+	}
+}
+
+/// GIMP のグラデーションファイル
+/// 各区間は「左端 中間 右端 左の色(RGBA) 右の色(RGBA) 補間方法 色空間」の順に並ぶ。
+/// HSV による補間は RGB として扱い、アルファは無視する。
+fn parse_ggr(name:&str,text:&str) -> Result<Gradient,String> {
+
+	let err = |line:&str| format!("{}: {}",name,line);
+
+	let mut lines = text.lines().skip(1);
+	let mut line = lines.next().ok_or(err(""))?;
+	let title = match line.strip_prefix("Name:") {
+		Some(t) => {
+			let t = String::from(t.trim());
+			line = lines.next().ok_or(err(""))?;
+			t
+		},
+		None => String::from(name)
+	};
+	let count:usize = line.trim().parse().map_err(|_| err(line))?;
+
+	let segments = lines.take(count).map(|line| {
+		let v:Vec<f64> = line.split_whitespace()
+			.map(|t| t.parse::<f64>())
+			.collect::<Result<_,_>>()
+			.map_err(|_| err(line))?;
+		if v.len()<11 { return Err(err(line)); }
+		let blend = match v.get(11).copied().unwrap_or(0.0) as u8 {
+			1 => Blend::Curved,
+			2 => Blend::Sine,
+			3 => Blend::SphereIncreasing,
+			4 => Blend::SphereDecreasing,
+			5 => Blend::Step,
+			_ => Blend::Linear
+		};
+		Ok(Segment {
+			left: v[0],
+			middle: v[1],
+			right: v[2],
+			left_color: (v[3],v[4],v[5]),
+			right_color: (v[7],v[8],v[9]),
+			blend
+		})
+	}).collect::<Result<Vec<_>,_>>()?;
+
+	match segments.len()==count && count>0 {
+		true  => Ok(Gradient { name:title, segments }),
+		false => Err(err("segments"))
+	}
+
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	/// 黒から白への1区間だけのグラデーション
+	fn ggr(blend:u8,coloring:u8) -> Gradient {
+		let text = format!("GIMP Gradient\nName: test\n1\n0 0.5 1 0 0 0 1 1 1 1 1 {} {}\n",blend,coloring);
+		parse_ggr("test.ggr",&text).unwrap()
+	}
+
+	fn close(a:f64,b:f64) -> bool {
+		(a-b).abs()<1e-9
+	}
+
+	#[test]
+	fn ggr_blend_types() {
+		assert!(close(ggr(0,0).at(0.25).0,0.25));
+		assert!(close(ggr(1,0).at(0.25).0,0.25));
+		assert!(close(ggr(2,0).at(0.25).0,(1.0-0.5f64.sqrt())/2.0));
+		assert!(close(ggr(3,0).at(0.5).0,0.75f64.sqrt()));
+		assert!(close(ggr(4,0).at(0.5).0,1.0-0.75f64.sqrt()));
+		assert!(close(ggr(5,0).at(0.49).0,0.0));
+		assert!(close(ggr(5,0).at(0.5).0,1.0));
+	}
+
+	#[test]
+	fn ggr_color_types() {
+		// HSV による補間も RGB として扱う
+		for coloring in [0,1,2] {
+			let g = ggr(0,coloring);
+			assert_eq!(g.name,"test");
+			assert!(close(g.at(0.5).0,0.5) && close(g.at(0.5).1,0.5) && close(g.at(0.5).2,0.5));
+		}
+	}
+
+	#[test]
+	fn ggr_segments() {
+		let text = "GIMP Gradient\n2\n0 0.25 0.5 1 0 0 1 0 1 0 1 0 0\n0.5 0.75 1 0 1 0 1 0 0 1 1\n";
+		let g = parse_ggr("two.ggr",text).unwrap();
+		assert_eq!(g.name,"two.ggr");
+		assert!(close(g.at(0.0).0,1.0) && close(g.at(0.5).1,1.0) && close(g.at(1.0).2,1.0));
+		assert!(parse_ggr("x","GIMP Gradient\n3\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n").is_err());
+		assert!(parse_ggr("x","GIMP Gradient\n1\n0 0.5 1 0 0 0 1 1 1 1\n").is_err());
+		assert!(parse_ggr("x","GIMP Gradient\nName: x\n").is_err());
+	}
+
+	#[test]
+	fn hex_list() {
+		let g = parse_hex_list("list","#ff0000, 00ff00\n0000FF\n").unwrap();
+		assert_eq!(g.at(0.0),(1.0,0.0,0.0));
+		assert_eq!(g.at(0.5),(0.0,1.0,0.0));
+		assert_eq!(g.at(1.0),(0.0,0.0,1.0));
+		assert_eq!(parse_hex_list("one","808080").unwrap().at(0.7),parse_hex_f64("808080").unwrap());
+		assert!(parse_hex_list("bad","ff0000 12345").is_err());
+		assert!(parse_hex_list("bad","ff0000 gg0000").is_err());
+		assert!(parse_hex_list("empty"," \n").is_err());
+	}
+
+	#[test]
+	fn builtin_palettes() {
+		for (name,_) in BUILTIN {
+			assert_eq!(Gradient::load(name).unwrap().name,*name);
+		}
+		let v = Gradient::load("viridis").unwrap();
+		assert_eq!(v.at(0.0),parse_hex_f64("440154").unwrap());
+		assert_eq!(v.at(1.0),parse_hex_f64("fde725").unwrap());
+	}
+
+}
@@ -0,0 +1,227 @@
+use std::fs::OpenOptions;
+use std::io::{Read,Write,ErrorKind,stdout};
+use std::os::unix::fs::OpenOptionsExt;
+use std::thread;
+use std::time::{Duration,Instant};
+use crossterm::{terminal,style::Stylize,style::Color,tty::IsTty};
+
+use crate::{
+	message::Msg,
+	draw_term::text_width,
+	scheme::{Scheme,JSON_NAMES}
+};
+
+#[allow(clippy::upper_case_acronyms)]
+type RGB = (u8,u8,u8);
+
+/// 応答を待つ間に読み込みを確かめる間隔
+const POLL_INTERVAL:Duration = Duration::from_millis(5);
+
+/// 端末に問い合わせた色
+/// 応答のなかった色は None とする。
+struct TerminalColors {
+	palette:    Vec<Option<RGB>>,
+	foreground: Option<RGB>,
+	background: Option<RGB>
+}
+
+/// 生成してから破棄するまでの間、端末を raw モードにする。
+/// 問い合わせの途中でエラーにより戻った場合も、破棄する際に元のモードに戻す。
+struct RawMode;
+
+impl RawMode {
+	fn enable() -> std::io::Result<RawMode> {
+		terminal::enable_raw_mode()?;
+		Ok(RawMode)
+	}
+}
+
+impl Drop for RawMode {
+	fn drop(&mut self) {
+		let _ = terminal::disable_raw_mode();
+	}
+}
+
+/// 端末の配色を OSC 4 (パレットの 0-255)、 OSC 10 (文字色)、 OSC 11 (背景色) で問い合わせ、
+/// xterm の既定値と比べた表、または JSON として表示する。
+pub fn query_colors(json:bool,timeout:u64) {
+
+	let colors = match query(Duration::from_millis(timeout)) {
+		Ok(c) => c,
+		Err(e) => error!(format!("{}\n{:?}",Msg::ErrorOccurred,e))
+	};
+
+	if colors.palette.iter().all(|c| c.is_none()) && colors.foreground.is_none() && colors.background.is_none() {
+		error!(Msg::QueryNoResponse);
+	}
+
+	match json {
+		true  => print_json(&colors),
+		false => print_table(&colors)
+	}
+
+}
+
+/// 端末 (/dev/tty) に問い合わせを送り、 timeout までに届いた応答を読む。
+/// 読み込みは raw モードを戻す前に終え、戻した後の入力を読み取らないようにする。
+/// 最後に DA1 (装置属性) を問い合わせ、その応答が届いた時点で全ての応答が揃ったとみなす。
+fn query(timeout:Duration) -> std::io::Result<TerminalColors> {
+
+	let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
+	// 応答がなくても戻れるように、読み込みは非ブロッキングで開いて一定の間隔で確かめる
+	let mut reader = OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open("/dev/tty")?;
+
+	let raw = RawMode::enable()?;
+
+	let mut request = String::new();
+	for i in 0..256 { request.push_str(&format!("\x1b]4;{};?\x1b\\",i)); }
+	request.push_str("\x1b]10;?\x1b\\\x1b]11;?\x1b\\\x1b[c");
+	tty.write_all(request.as_bytes())?;
+	tty.flush()?;
+
+	let deadline = Instant::now()+timeout;
+	let mut response:Vec<u8> = vec![];
+	let mut buf = [0u8;4096];
+	while Instant::now()<deadline && !has_device_attributes(&response) {
+		match reader.read(&mut buf) {
+			Ok(0) => break,
+			Ok(n) => response.extend_from_slice(&buf[..n]),
+			Err(e) if e.kind()==ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+			Err(e) if e.kind()==ErrorKind::Interrupted => {},
+			Err(e) => return Err(e)
+		}
+	}
+
+	drop(raw);
+
+	Ok(parse_response(&String::from_utf8_lossy(&response)))
+
+}
+
+/// DA1 の応答 (ESC [ ? 数字と ; の並び c) が含まれているかどうか
+fn has_device_attributes(response:&[u8]) -> bool {
+	(0..response.len()).filter(|i| response[*i..].starts_with(b"\x1b[?")).any(|i| {
+		response[i+3..].iter()
+			.find(|b| !(b.is_ascii_digit() || **b==b';'))
+			.map(|b| *b==b'c')
+			.unwrap_or(false)
+	})
+}
+
+/// OSC の応答 (ESC ] 4;番号;rgb:RRRR/GGGG/BBBB ST、 ESC ] 10;rgb:… ST など) を読む。
+/// ST は ESC \ または BEL とする。
+fn parse_response(text:&str) -> TerminalColors {
+
+	let mut colors = TerminalColors { palette:vec![None;256], foreground:None, background:None };
+
+	for body in text.split("\x1b]").skip(1) {
+		let body = match body.find(['\x07','\x1b']) {
+			Some(i) => &body[..i],
+			None => continue
+		};
+		let fields:Vec<&str> = body.split(';').collect();
+		match fields.as_slice() {
+			["4",i,spec] => if let (Ok(i),Some(c)) = (i.parse::<u8>(),parse_rgb(spec)) {
+				colors.palette[i as usize] = Some(c);
+			},
+			["10",spec] => colors.foreground = parse_rgb(spec),
+			["11",spec] => colors.background = parse_rgb(spec),
+			_ => {}
+		}
+	}
+
+	colors
+
+}
+
+/// "rgb:RRRR/GGGG/BBBB" (各成分は1から4桁の16進数) を8bitの値にする。
+fn parse_rgb(spec:&str) -> Option<RGB> {
+	let v:Vec<u8> = spec.strip_prefix("rgb:")?
+		.split('/')
+		.map(|h| {
+			let max = (1u32<<(4*h.len()))-1;
+			let v = u32::from_str_radix(h,16).ok()?;
+			match h.len() {
+				1..=4 => Some(((v as f64)*255.0/(max as f64)).round() as u8),
+				_ => None
+			}
+		})
+		.collect::<Option<_>>()?;
+	match v.as_slice() {
+		[r,g,b] => Some((*r,*g,*b)),
+		_ => None
+	}
+}
+
+fn hex(c:RGB) -> String {
+	format!("#{:02x}{:02x}{:02x}",c.0,c.1,c.2)
+}
+
+/// 文字色、背景色、 0-15 の色は常に、 16-255 は xterm の既定値と異なる色だけを表示する。
+/// 出力が端末の場合は実際の色の見本を並べる。
+fn print_table(colors:&TerminalColors) {
+
+	let xterm = Scheme::xterm();
+	let swatch = stdout().is_tty();
+	let mut differ = 0;
+
+	// 全角文字の見出しも右に揃える
+	let right = |text:String,width:usize| format!("{}{}"," ".repeat(width.saturating_sub(text_width(&text))),text);
+	println!("{}  {}  {}",right(Msg::QueryIndex.to_string(),10),right(String::from("xterm"),8),right(Msg::QueryActual.to_string(),8));
+
+	let rows = [("fg",colors.foreground,xterm.foreground),("bg",colors.background,xterm.background)].into_iter()
+		.map(|(n,a,e)| (String::from(n),a,e))
+		.chain((0..=255u8).map(|i| (i.to_string(),colors.palette[i as usize],xterm.color(i))));
+
+	for (i,(name,actual,expected)) in rows.enumerate() {
+		let differs = actual.map(|a| a!=expected).unwrap_or(false);
+		if differs { differ += 1; }
+		if i>=18 && !differs { continue; }
+		let text = actual.map(hex).unwrap_or(String::from("-"));
+		let sample = match (swatch,actual) {
+			(true,Some((r,g,b))) => format!("{}","    ".on(Color::Rgb{r,g,b})),
+			_ => String::new()
+		};
+		println!(
+			"{:>10}  {:>8}  {:>8} {} {}",
+			name,hex(expected),text,if differs { "*" } else { " " },sample
+		);
+	}
+
+	let missing = colors.palette.iter().filter(|c| c.is_none()).count();
+	println!();
+	println!("{} {}",Msg::QueryDiffer,differ);
+	if missing>0 { println!("{} {}",Msg::QueryMissing,missing); }
+
+}
+
+/// Windows Terminal の配色と同じ名前で 0-15 と文字色、背景色を出力し、続けて 0-255 の全ての色と
+/// xterm の既定値と異なる番号を並べる。応答のなかった色は名前ごと省き、 palette では null とする。
+/// 文字色、背景色と 0-15 の全てに応答があれば、出力はそのまま --ansi-scheme に指定できる。
+fn print_json(colors:&TerminalColors) {
+
+	let xterm = Scheme::xterm();
+	let value = |c:Option<RGB>| c.map(|c| format!("\"{}\"",hex(c))).unwrap_or(String::from("null"));
+
+	let mut fields:Vec<String> = vec![String::from("\"name\": \"terminal\"")];
+	let named = [("foreground",colors.foreground),("background",colors.background)].into_iter()
+		.chain(JSON_NAMES.iter().zip(&colors.palette).map(|(n,c)| (*n,*c)));
+	for (n,c) in named {
+		if let Some(c) = c { fields.push(format!("\"{}\": \"{}\"",n,hex(c))); }
+	}
+
+	let palette:Vec<String> = colors.palette.iter().map(|c| value(*c)).collect();
+	fields.push(format!("\"palette\": [{}]",palette.join(", ")));
+
+	let differs:Vec<String> = [("\"foreground\"",colors.foreground,xterm.foreground),("\"background\"",colors.background,xterm.background)].iter()
+		.filter(|(_,a,e)| a.map(|a| a!=*e).unwrap_or(false))
+		.map(|(n,_,_)| String::from(*n))
+		.chain((0..=255u8)
+			.filter(|i| colors.palette[*i as usize].map(|a| a!=xterm.color(*i)).unwrap_or(false))
+			.map(|i| i.to_string()))
+		.collect();
+	fields.push(format!("\"differs\": [{}]",differs.join(", ")));
+
+	println!("{{\n\t{}\n}}",fields.join(",\n\t"));
+
+}
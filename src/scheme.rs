@@ -0,0 +1,227 @@
+use std::fs::read_to_string;
+use std::collections::HashMap;
+use serde_json::Value;
+
+use crate::library::parse_hex;
+
+/// 組み込みの端末の配色
+/// ANSI カラーの 0-15 に続けて、既定の文字色と背景色を並べる。
+/// solarized は Solarized Dark、 tango は GNOME 端末の Tango (暗い背景) による。
+const BUILTIN:&[(&str,[&str;18])] = &[
+	("xterm",[
+		"000000","cd0000","00cd00","cdcd00","0000ee","cd00cd","00cdcd","e5e5e5",
+		"7f7f7f","ff0000","00ff00","ffff00","5c5cff","ff00ff","00ffff","ffffff",
+		"000000","ffffff"
+	]),
+	("vga",[
+		"000000","aa0000","00aa00","aa5500","0000aa","aa00aa","00aaaa","aaaaaa",
+		"555555","ff5555","55ff55","ffff55","5555ff","ff55ff","55ffff","ffffff",
+		"aaaaaa","000000"
+	]),
+	("solarized",[
+		"073642","dc322f","859900","b58900","268bd2","d33682","2aa198","eee8d5",
+		"002b36","cb4b16","586e75","657b83","839496","6c71c4","93a1a1","fdf6e3",
+		"839496","002b36"
+	]),
+	("tango",[
+		"000000","cc0000","4e9a06","c4a000","3465a4","75507b","06989a","d3d7cf",
+		"555753","ef2929","8ae234","fce94f","729fcf","ad7fa8","34e2e2","eeeeec",
+		"d3d7cf","2e3436"
+	])
+];
+
+/// Windows Terminal の配色の ANSI カラーの 0-15 の名前
+pub const JSON_NAMES:[&str;16] = [
+	"black","red","green","yellow","blue","purple","cyan","white",
+	"brightBlack","brightRed","brightGreen","brightYellow","brightBlue","brightPurple","brightCyan","brightWhite"
+];
+
+/// 端末の配色
+/// ANSI カラーの 0-15 と、既定の文字色と背景色を持つ。 16-255 は xterm の256色パレットと同じとする。
+pub struct Scheme {
+	pub name: String,
+	colors: [(u8,u8,u8);16],
+	pub foreground: (u8,u8,u8),
+	pub background: (u8,u8,u8)
+}
+
+impl Scheme {
+
+	/// 組み込みの配色名、または iTerm2 の配色 (.itermcolors) か Windows Terminal の配色 (JSON) のパスから読み込む。
+	pub fn load(spec:&str) -> Result<Scheme,String> {
+		if let Some((name,colors)) = BUILTIN.iter().find(|(n,_)| *n==spec) {
+			let c:Vec<(u8,u8,u8)> = colors.iter().map(|c| parse_hex(c).unwrap()).collect();
+			return Ok(Scheme::from_colors(name,&c));
+		}
+		let text = read_to_string(spec).map_err(|e| format!("{}: {}",spec,e))?;
+		match text.trim_start().starts_with('{') {
+			true  => parse_json(spec,&text),
+			false => parse_itermcolors(spec,&text)
+		}
+	}
+
+	/// 既定の配色 (xterm)
+	pub fn xterm() -> Scheme {
+		Scheme::load("xterm").unwrap()
+	}
+
+	/// 0-15 の色、文字色、背景色の順に並べた18色から生成する。
+	fn from_colors(name:&str,c:&[(u8,u8,u8)]) -> Scheme {
+		let mut colors = [(0,0,0);16];
+		colors.copy_from_slice(&c[..16]);
+		Scheme { name:String::from(name), colors, foreground:c[16], background:c[17] }
+	}
+
+	/// ANSI カラーの番号 v の色
+	pub fn color(&self,v:u8) -> (u8,u8,u8) {
+		match v {
+			0..=15 => self.colors[v as usize],
+			_ => extended_color(v)
+		}
+	}
+
+}
+
+/// xterm の256色パレットの 16-255 の色
+/// 16-231 は 6×6×6 の色立方体、 232-255 は24段階の灰色。
+fn extended_color(v:u8) -> (u8,u8,u8) {
+	let level = |i:u8| if i==0 { 0 } else { 55+40*i };
+	match v {
+		0..=231 => {
+			let i = v.saturating_sub(16);
+			(level(i/36),level(i/6%6),level(i%6))
+		},
+		_ => {
+			let g = 8+10*(v-232);
+			(g,g,g)
+		}
+	}
+}
+
+/// Windows Terminal の配色
+/// "black": "#0C0C0C" のような名前と16進数の色の組を持つ1つのオブジェクトとして読む。
+fn parse_json(name:&str,text:&str) -> Result<Scheme,String> {
+
+	let err = |key:&str| format!("{}: {}",name,key);
+
+	let object:HashMap<String,Value> = serde_json::from_str(text).map_err(|e| err(&e.to_string()))?;
+	let value = |key:&str| object.get(key).and_then(Value::as_str);
+
+	let title = value("name").unwrap_or(name);
+	let mut colors = JSON_NAMES.iter()
+		.map(|k| value(k).and_then(parse_hex).ok_or(err(k)))
+		.collect::<Result<Vec<_>,_>>()?;
+	for k in ["foreground","background"] {
+		colors.push(value(k).and_then(parse_hex).ok_or(err(k))?);
+	}
+
+	Ok(Scheme::from_colors(title,&colors))
+
+}
+
+/// iTerm2 の配色 (XML の plist)
+/// <key>Ansi 0 Color</key> に続く <dict> の Red/Green/Blue Component を 0 から 1 の実数として読む。
+fn parse_itermcolors(name:&str,text:&str) -> Result<Scheme,String> {
+
+	let err = |key:&str| format!("{}: {}",name,key);
+
+	let entry = |key:&str| -> Option<(u8,u8,u8)> {
+		let start = text.find(&format!("<key>{}</key>",key))?;
+		let dict = &text[start..];
+		let dict = &dict[..dict.find("</dict>")?];
+		let component = |c:&str| -> Option<u8> {
+			let rest = &dict[dict.find(&format!("<key>{} Component</key>",c))?..];
+			let rest = &rest[rest.find("<real>")?+6..];
+			let v:f64 = rest[..rest.find("</real>")?].trim().parse().ok()?;
+			Some((v.clamp(0.0,1.0)*255.0).round() as u8)
+		};
+		Some((component("Red")?,component("Green")?,component("Blue")?))
+	};
+
+	let keys = (0..16).map(|i| format!("Ansi {} Color",i))
+		.chain([String::from("Foreground Color"),String::from("Background Color")]);
+	let colors = keys
+		.map(|k| entry(&k).ok_or(err(&k)))
+		.collect::<Result<Vec<_>,_>>()?;
+
+	Ok(Scheme::from_colors(name,&colors))
+
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	/// i 番目の色を (i, 2i, 3i) とした Windows Terminal の配色
+	fn json(title:&str) -> String {
+		let colors:Vec<String> = JSON_NAMES.iter().enumerate()
+			.map(|(i,k)| format!("\"{}\": \"#{:02x}{:02x}{:02X}\"",k,i,2*i,3*i))
+			.collect();
+		format!(
+			"{{\n\t\"name\": \"{}\",\n\t{},\n\t\"foreground\": \"#FFFFFF\",\n\t\"background\": \"#000000\"\n}}\n",
+			title,colors.join(",\n\t")
+		)
+	}
+
+	#[test]
+	fn windows_terminal_json() {
+		let s = parse_json("scheme.json",&json("Campbell")).unwrap();
+		assert_eq!(s.name,"Campbell");
+		for i in 0..16 {
+			assert_eq!(s.color(i),(i,2*i,3*i));
+		}
+		assert_eq!(s.foreground,(255,255,255));
+		assert_eq!(s.background,(0,0,0));
+	}
+
+	#[test]
+	fn windows_terminal_json_escapes() {
+		// 名前に含まれる引用符やキーに似た文字列で、名前と色の組がずれない
+		let s = parse_json("scheme.json",&json(r##"A \"red\": \"#ff0000\" scheme"##)).unwrap();
+		assert_eq!(s.name,r##"A "red": "#ff0000" scheme"##);
+		assert_eq!(s.color(1),(1,2,3));
+	}
+
+	#[test]
+	fn windows_terminal_json_errors() {
+		let text = json("x").replace("\"brightRed\"","\"bright_red\"");
+		assert_eq!(parse_json("s.json",&text).err().unwrap(),"s.json: brightRed");
+		let text = json("x").replace("\"background\": \"#000000\"","\"background\": \"#00000\"");
+		assert_eq!(parse_json("s.json",&text).err().unwrap(),"s.json: background");
+		assert!(parse_json("s.json","{ \"black\": ").is_err());
+	}
+
+	/// i 番目の色を赤 i/15、緑 0.5、青 1 とした iTerm2 の配色
+	fn plist() -> String {
+		let keys = (0..16).map(|i| format!("Ansi {} Color",i))
+			.chain([String::from("Foreground Color"),String::from("Background Color")]);
+		let dicts:Vec<String> = keys.enumerate().map(|(i,k)| format!(
+			"\t<key>{}</key>\n\t<dict>\n\t\t<key>Alpha Component</key>\n\t\t<real>1</real>\n\t\t<key>Blue Component</key>\n\t\t<real>1.0</real>\n\t\t<key>Green Component</key>\n\t\t<real>0.5</real>\n\t\t<key>Red Component</key>\n\t\t<real>{}</real>\n\t</dict>\n",
+			k,(i.min(15) as f64)/15.0
+		)).collect();
+		format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n<dict>\n{}</dict>\n</plist>\n",dicts.concat())
+	}
+
+	#[test]
+	fn itermcolors() {
+		let s = parse_itermcolors("dark.itermcolors",&plist()).unwrap();
+		assert_eq!(s.name,"dark.itermcolors");
+		assert_eq!(s.color(0),(0,128,255));
+		assert_eq!(s.color(5),(85,128,255));
+		assert_eq!(s.color(15),(255,128,255));
+		assert_eq!(s.foreground,(255,128,255));
+		let text = plist().replacen("<key>Ansi 3 Color</key>","<key>Ansi 3 Colour</key>",1);
+		assert_eq!(parse_itermcolors("x",&text).err().unwrap(),"x: Ansi 3 Color");
+	}
+
+	#[test]
+	fn builtin_schemes() {
+		let x = Scheme::xterm();
+		assert_eq!(x.color(1),(205,0,0));
+		assert_eq!(x.color(16),(0,0,0));
+		assert_eq!(x.color(231),(255,255,255));
+		assert_eq!(x.color(244),(128,128,128));
+	}
+
+}